@@ -0,0 +1,91 @@
+use crate::dpop::{self, CleDpop};
+use anyhow::Error;
+use serde_json::Value;
+use std::fmt;
+use std::iter;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Methode {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl Methode {
+    pub const ALL: [Methode; 5] = [Methode::Get, Methode::Post, Methode::Put, Methode::Patch, Methode::Delete];
+}
+
+impl fmt::Display for Methode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let methode = match self {
+            Methode::Get => "GET",
+            Methode::Post => "POST",
+            Methode::Put => "PUT",
+            Methode::Patch => "PATCH",
+            Methode::Delete => "DELETE",
+        };
+        f.write_str(methode)
+    }
+}
+
+// Les entêtes sont saisies une par ligne: Nom: valeur
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequeteApi {
+    pub methode: Methode,
+    pub url: String,
+    pub entetes: String,
+    pub corps: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReponseApi {
+    pub statut: u16,
+    pub entetes: Vec<Vec<String>>,
+    pub corps: Vec<Vec<String>>,
+}
+
+// Un statut d'erreur est affiché comme une réponse
+pub fn appeler(requete: &RequeteApi, secret: &str, cle: Option<&CleDpop>) -> Result<ReponseApi, Error> {
+    let construire = || {
+        let appel = ureq::request(&requete.methode.to_string(), requete.url.trim()).timeout(Duration::from_secs(20));
+        requete
+            .entetes
+            .lines()
+            .filter_map(|ligne| ligne.split_once(':'))
+            .fold(appel, |appel, (k, v)| appel.set(k.trim(), v.trim()))
+    };
+    let corps = Some(requete.corps.as_str()).filter(|corps| !corps.is_empty());
+
+    let resultat = dpop::envoyer(construire, secret, cle, corps);
+    let response = match resultat {
+        Ok(response) => response,
+        Err(e) => match *e {
+            ureq::Error::Status(_, response) => response,
+            e => return Err(e.into()),
+        },
+    };
+
+    let statut = response.status();
+    let entetes = iter::once(vec!["Entête".to_owned(), "Valeur".to_owned()])
+        .chain(
+            response
+                .headers_names()
+                .into_iter()
+                .map(|nom| vec![nom.clone(), response.header(&nom).unwrap_or_default().to_owned()]),
+        )
+        .collect();
+
+    let corps = response.into_string()?;
+    let corps = match serde_json::from_str::<Value>(&corps) {
+        Ok(Value::Object(map)) => iter::once(vec!["Propriété".to_owned(), "Valeur".to_owned()])
+            .chain(map.iter().map(|(k, v)| vec![k.to_owned(), v.to_string().replace('"', "")]))
+            .collect(),
+        _ => vec![vec!["Corps".to_owned()], vec![corps]],
+    };
+
+    Ok(ReponseApi { statut, entetes, corps })
+}
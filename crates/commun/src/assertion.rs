@@ -0,0 +1,167 @@
+use crate::dpop::jti;
+use anyhow::{anyhow, Context, Error};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TYPE_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+const TYPE_OBJET_REQUETE: &str = "oauth-authz-req+jwt";
+
+// Méthodes d'authentification du client au token endpoint (OIDC Core 9)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MethodeAuth {
+    #[default]
+    ClientSecretPost,
+    ClientSecretBasic,
+    ClientSecretJwt,
+    PrivateKeyJwt,
+}
+
+impl MethodeAuth {
+    pub const ALL: [MethodeAuth; 4] = [
+        MethodeAuth::ClientSecretPost,
+        MethodeAuth::ClientSecretBasic,
+        MethodeAuth::ClientSecretJwt,
+        MethodeAuth::PrivateKeyJwt,
+    ];
+}
+
+impl fmt::Display for MethodeAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let methode = match self {
+            MethodeAuth::ClientSecretPost => "client_secret_post",
+            MethodeAuth::ClientSecretBasic => "client_secret_basic",
+            MethodeAuth::ClientSecretJwt => "client_secret_jwt",
+            MethodeAuth::PrivateKeyJwt => "private_key_jwt",
+        };
+        f.write_str(methode)
+    }
+}
+
+// Pushed Authorization Request (RFC 9126): Auto si l'OP annonce pushed_authorization_request_endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModePar {
+    #[default]
+    Auto,
+    Toujours,
+    Jamais,
+}
+
+impl ModePar {
+    pub const ALL: [ModePar; 3] = [ModePar::Auto, ModePar::Toujours, ModePar::Jamais];
+}
+
+impl fmt::Display for ModePar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            ModePar::Auto => "PAR si annoncé",
+            ModePar::Toujours => "PAR toujours",
+            ModePar::Jamais => "PAR jamais",
+        };
+        f.write_str(mode)
+    }
+}
+
+// Méthode choisie pour un fournisseur et chemin de la clé PEM pour private_key_jwt ou la requête signée (JAR).
+// Avec dpop, le jeton est lié à une clé éphémère (RFC 9449).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthClient {
+    pub methode: MethodeAuth,
+    pub cle: String,
+    pub jar: bool,
+    pub par: ModePar,
+    pub dpop: bool,
+}
+
+// Assertion client_secret_jwt (HS256 avec le secret) ou private_key_jwt (RS256 ou ES256 selon la clé)
+fn assertion(auth: &AuthClient, id: &str, secret: &str, url_token: &str) -> Result<Option<String>, Error> {
+    let (cle, entete) = match auth.methode {
+        MethodeAuth::ClientSecretJwt => (EncodingKey::from_secret(secret.as_bytes()), Header::new(Algorithm::HS256)),
+        MethodeAuth::PrivateKeyJwt => cle_privee(auth)?,
+        _ => return Ok(None),
+    };
+
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let claims = json!({
+        "iss": id,
+        "sub": id,
+        "aud": url_token,
+        "jti": jti(),
+        "iat": maintenant,
+        "exp": maintenant + 300,
+    });
+
+    Ok(Some(encode(&entete, &claims, &cle)?))
+}
+
+// Paramètres client_assertion_type et client_assertion à ajouter à la requête au token endpoint
+pub fn parametres_assertion(auth: &AuthClient, id: &str, secret: &str, url_token: &str) -> Result<Vec<(&'static str, String)>, Error> {
+    Ok(match assertion(auth, id, secret, url_token)? {
+        Some(jwt) => vec![("client_assertion_type", TYPE_ASSERTION.to_owned()), ("client_assertion", jwt)],
+        None => Vec::new(),
+    })
+}
+
+// Objet request signé avec la clé privée (RFC 9101): les claims reprennent les paramètres de la requête d'autorisation
+pub fn objet_requete(auth: &AuthClient, parametres: &[(String, String)], issuer: &str) -> Result<String, Error> {
+    let (cle, mut entete) = cle_privee(auth)?;
+    entete.typ = Some(TYPE_OBJET_REQUETE.to_owned());
+
+    let mut claims = parametres
+        .iter()
+        .map(|(k, v)| (k.to_owned(), Value::from(v.as_str())))
+        .collect::<Map<_, _>>();
+    let client_id = claims.get("client_id").cloned().unwrap_or_default();
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    claims.insert("iss".to_owned(), client_id);
+    claims.insert("aud".to_owned(), Value::from(issuer));
+    claims.insert("jti".to_owned(), Value::from(jti()));
+    claims.insert("iat".to_owned(), Value::from(maintenant));
+    claims.insert("nbf".to_owned(), Value::from(maintenant));
+    claims.insert("exp".to_owned(), Value::from(maintenant + 300));
+
+    Ok(encode(&entete, &claims, &cle)?)
+}
+
+// RS256 pour une clé RSA, sinon ES256
+fn cle_privee(auth: &AuthClient) -> Result<(EncodingKey, Header), Error> {
+    if auth.cle.is_empty() {
+        return Err(anyhow!("une clé privée est requise"));
+    }
+    let pem = std::fs::read(&auth.cle).with_context(|| format!("clé privée {}", auth.cle))?;
+    Ok(match EncodingKey::from_rsa_pem(&pem) {
+        Ok(cle) => (cle, Header::new(Algorithm::RS256)),
+        Err(_) => (
+            EncodingKey::from_ec_pem(&pem).context("clé privée RSA ou EC P-256")?,
+            Header::new(Algorithm::ES256),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, DecodingKey, Validation};
+
+    #[test]
+    fn client_secret_jwt() {
+        let auth = AuthClient {
+            methode: MethodeAuth::ClientSecretJwt,
+            ..Default::default()
+        };
+        let parametres = parametres_assertion(&auth, "client", "secret", "https://op/token").unwrap();
+        assert_eq!(parametres[0], ("client_assertion_type", TYPE_ASSERTION.to_owned()));
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&["https://op/token"]);
+        let claims = decode::<Map<String, Value>>(&parametres[1].1, &DecodingKey::from_secret(b"secret"), &validation)
+            .unwrap()
+            .claims;
+        assert_eq!(claims["iss"], "client");
+        assert_eq!(claims["sub"], "client");
+
+        let post = AuthClient::default();
+        assert!(parametres_assertion(&post, "client", "secret", "https://op/token").unwrap().is_empty());
+    }
+}
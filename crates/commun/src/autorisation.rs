@@ -0,0 +1,45 @@
+// Scopes proposés par les clients de bureau; d'autres peuvent être saisis
+pub const SCOPES: [&str; 4] = ["openid", "email", "profile", "offline_access"];
+
+// Paramètres additionnels admis dans la requête d'autorisation
+pub const PARAMETRES_AUTORISATION: [&str; 7] = ["prompt", "login_hint", "max_age", "acr_values", "ui_locales", "claims", "response_mode"];
+
+// Scopes et paramètres additionnels de la requête d'autorisation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Demande {
+    pub scopes: Vec<String>,
+    pub parametres: Vec<(String, String)>,
+}
+
+impl Demande {
+    // Les autres scopes sont séparés par des espaces et les paramètres saisis comme une query string: prompt=consent&login_hint=...
+    pub fn saisie(scopes: impl IntoIterator<Item = String>, autres_scopes: &str, parametres: &str) -> Self {
+        let scopes = scopes.into_iter().chain(autres_scopes.split_whitespace().map(str::to_owned)).collect();
+
+        let parametres = url::form_urlencoded::parse(parametres.trim().as_bytes())
+            .filter(|(k, v)| PARAMETRES_AUTORISATION.contains(&&**k) && !v.is_empty())
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+
+        Demande { scopes, parametres }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saisie() {
+        let demande = Demande::saisie(
+            ["openid".to_owned()],
+            " User.Read  Mail.Read ",
+            "prompt=consent&lol=bouh&max_age=&login_hint=a%40b.c",
+        );
+        assert_eq!(demande.scopes, ["openid", "User.Read", "Mail.Read"]);
+        assert_eq!(
+            demande.parametres,
+            [("prompt".to_owned(), "consent".to_owned()), ("login_hint".to_owned(), "a@b.c".to_owned())]
+        );
+    }
+}
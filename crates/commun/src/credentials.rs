@@ -0,0 +1,62 @@
+use crate::api::{appeler, RequeteApi};
+use crate::jwt;
+use anyhow::Error;
+use serde_json::{Map, Value};
+use std::iter;
+
+// Lignes du jeton et, si demandée, de la réponse de la ressource protégée
+pub type Reponse = (Vec<Vec<String>>, Option<Vec<Vec<String>>>);
+
+// Grant client_credentials: scopes séparés par des espaces, resource (RFC 8707) et ressource protégée optionnels
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientCredentials {
+    pub scopes: String,
+    pub resource: String,
+    pub url: String,
+}
+
+// Les champs de la réponse du token endpoint sont complétés par les claims d'un jeton JWT
+pub fn reponse(cc: &ClientCredentials, mut map: Map<String, Value>, jeton: &str) -> Result<Reponse, Error> {
+    match jwt::inspecter(jeton) {
+        jwt::FormatJeton::Jwt { charge, .. } => {
+            map.insert("format".into(), Value::String("JWT".into()));
+            map.extend(charge);
+        }
+        jwt::FormatJeton::Opaque => {
+            map.insert("format".into(), Value::String("opaque".into()));
+        }
+    }
+
+    let ressource = match cc.url.trim() {
+        "" => None,
+        url => Some(appeler_ressource(url, jeton)?),
+    };
+
+    Ok((lignes(&map), ressource))
+}
+
+// GET de la ressource protégée: statut suivi du corps
+fn appeler_ressource(url: &str, secret: &str) -> Result<Vec<Vec<String>>, Error> {
+    let requete = RequeteApi {
+        url: url.to_owned(),
+        ..Default::default()
+    };
+    let reponse = appeler(&requete, secret, None)?;
+    let statut = vec!["statut".to_owned(), reponse.statut.to_string()];
+    let mut corps = reponse.corps.into_iter();
+    Ok(match corps.next() {
+        // Corps non JSON: une seule colonne
+        Some(entete) if entete.len() == 1 => vec![
+            vec!["Propriété".to_owned(), "Valeur".to_owned()],
+            statut,
+            vec!["corps".to_owned(), corps.flatten().collect()],
+        ],
+        entete => entete.into_iter().chain(iter::once(statut)).chain(corps).collect(),
+    })
+}
+
+fn lignes(map: &Map<String, Value>) -> Vec<Vec<String>> {
+    iter::once(vec!["Propriété".to_owned(), "Valeur".to_owned()])
+        .chain(map.iter().map(|(k, v)| vec![k.to_owned(), v.to_string().replace('"', "")]))
+        .collect()
+}
@@ -0,0 +1,236 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+// OAuth 2.0 Demonstrating Proof of Possession (RFC 9449)
+const TYPE_PREUVE: &str = "dpop+jwt";
+
+// Dernier DPoP-Nonce reçu de chaque serveur (origine), AS ou serveur de ressources (8 et 9)
+static NONCES: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// Clé P-256 éphémère générée pour une session ou un jeton; le jeton obtenu y est lié par cnf.jkt
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CleDpop {
+    pkcs8: String,
+    x: String,
+    y: String,
+}
+
+// La clé privée n'est pas affichée
+impl fmt::Debug for CleDpop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CleDpop").field("jkt", &self.jkt()).finish()
+    }
+}
+
+impl CleDpop {
+    pub fn generer() -> Self {
+        let aleatoire = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &aleatoire).expect("génération P-256");
+        let paire = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &aleatoire).expect("clé PKCS#8 générée");
+        // Point non compressé: 0x04 || x || y
+        let publique = paire.public_key().as_ref();
+        Self {
+            pkcs8: URL_SAFE_NO_PAD.encode(pkcs8.as_ref()),
+            x: URL_SAFE_NO_PAD.encode(&publique[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&publique[33..65]),
+        }
+    }
+
+    // Empreinte JWK (RFC 7638): membres requis dans l'ordre lexicographique
+    pub fn jkt(&self) -> String {
+        let jwk = format!(r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#, self.x, self.y);
+        URL_SAFE_NO_PAD.encode(digest(&SHA256, jwk.as_bytes()))
+    }
+
+    // Preuve DPoP (4.2): htu est l'URL sans query ni fragment; ath lie la preuve au jeton d'accès présenté
+    pub fn preuve(&self, htm: &str, htu: &str, jeton: Option<&str>) -> String {
+        let htu = match Url::parse(htu) {
+            Ok(mut url) => {
+                url.set_query(None);
+                url.set_fragment(None);
+                url.to_string()
+            }
+            Err(_) => htu.split(['?', '#']).next().unwrap_or_default().to_owned(),
+        };
+
+        let mut entete = Header::new(Algorithm::ES256);
+        entete.typ = Some(TYPE_PREUVE.to_owned());
+        entete.jwk = serde_json::from_value::<Jwk>(json!({ "kty": "EC", "crv": "P-256", "x": self.x, "y": self.y })).ok();
+
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut claims = json!({
+            "jti": jti(),
+            "htm": htm,
+            "htu": htu,
+            "iat": maintenant,
+        });
+        if let Some(jeton) = jeton {
+            claims["ath"] = json!(URL_SAFE_NO_PAD.encode(digest(&SHA256, jeton.as_bytes())));
+        }
+        if let Some(nonce) = origine(&htu).and_then(|origine| NONCES.read().expect("Failed due to poisoned lock").get(&origine).cloned()) {
+            claims["nonce"] = json!(nonce);
+        }
+
+        let pkcs8 = URL_SAFE_NO_PAD.decode(&self.pkcs8).expect("clé PKCS#8 encodée par generer");
+        encode(&entete, &claims, &EncodingKey::from_ec_der(&pkcs8)).expect("signature ES256")
+    }
+}
+
+pub(crate) fn jti() -> String {
+    let mut octets = [0u8; 32];
+    SystemRandom::new().fill(&mut octets).expect("aléatoire");
+    URL_SAFE_NO_PAD.encode(octets)
+}
+
+fn origine(url: &str) -> Option<String> {
+    Url::parse(url).ok().map(|url| url.origin().ascii_serialization())
+}
+
+// Conserve le dernier nonce reçu de l'origine; il est repris par les preuves suivantes
+fn conserver_nonce(url: &str, nonce: Option<&str>) {
+    if let (Some(nonce), Some(origine)) = (nonce, origine(url)) {
+        NONCES.write().expect("Failed due to poisoned lock").insert(origine, nonce.to_owned());
+    }
+}
+
+// L'AS exige un nonce par une erreur 400 use_dpop_nonce (8); toute autre erreur n'est pas rejouée
+fn nonce_exige_as(statut: u16, corps: &[u8]) -> bool {
+    statut == 400
+        && serde_json::from_slice::<Value>(corps)
+            .ok()
+            .is_some_and(|corps| corps.get("error").and_then(Value::as_str) == Some("use_dpop_nonce"))
+}
+
+// Le serveur de ressources l'exige par une erreur 401 dont WWW-Authenticate porte error="use_dpop_nonce" (9)
+fn nonce_exige_rs(statut: u16, www_authenticate: Option<&str>) -> bool {
+    statut == 401 && www_authenticate.is_some_and(|entete| entete.contains(r#"error="use_dpop_nonce""#))
+}
+
+// Réponse du token endpoint: le DPoP-Nonce est conservé; vrai si la requête doit être rejouée avec ce nonce
+pub fn rejouer_as(url: &str, statut: u16, nonce: Option<&str>, corps: &[u8]) -> bool {
+    conserver_nonce(url, nonce);
+    nonce.is_some() && nonce_exige_as(statut, corps)
+}
+
+// Réponse du serveur de ressources: même règle, l'exigence est portée par WWW-Authenticate
+pub fn rejouer_rs(url: &str, statut: u16, nonce: Option<&str>, www_authenticate: Option<&str>) -> bool {
+    conserver_nonce(url, nonce);
+    nonce.is_some() && nonce_exige_rs(statut, www_authenticate)
+}
+
+// cnf.jkt du jeton comparé à l'empreinte de la clé (6.1)
+pub fn liaison(cle: &CleDpop, charge: Option<&Map<String, Value>>) -> String {
+    let jkt = cle.jkt();
+    let etat = match charge.map(|charge| charge.get("cnf").and_then(|cnf| cnf.get("jkt")).and_then(Value::as_str)) {
+        None => "cnf.jkt non vérifiable".to_owned(),
+        Some(None) => "cnf.jkt absent du jeton".to_owned(),
+        Some(Some(cnf)) if cnf == jkt => "cnf.jkt correspond".to_owned(),
+        Some(Some(cnf)) => format!("cnf.jkt différent: {cnf}"),
+    };
+    format!("Lié par DPoP à la clé {jkt}: {etat}")
+}
+
+// Requête avec le jeton d'accès: DPoP avec une preuve liée au jeton si une clé est fournie, sinon Bearer
+#[cfg(feature = "client")]
+pub fn envoyer(
+    construire: impl Fn() -> ureq::Request,
+    jeton: &str,
+    cle: Option<&CleDpop>,
+    corps: Option<&str>,
+) -> Result<ureq::Response, Box<ureq::Error>> {
+    let appeler = |requete: ureq::Request| {
+        match corps {
+            Some(corps) => requete.send_string(corps),
+            None => requete.call(),
+        }
+        .map_err(Box::new)
+    };
+    let Some(cle) = cle else {
+        return appeler(construire().set("Authorization", &format!("Bearer {jeton}")));
+    };
+
+    let mut rejouee = false;
+    loop {
+        let requete = construire();
+        let url = requete.url().to_owned();
+        let preuve = cle.preuve(requete.method(), &url, Some(jeton));
+        let resultat = appeler(requete.set("Authorization", &format!("DPoP {jeton}")).set("DPoP", &preuve));
+
+        let rejouer = match resultat.as_ref().map_err(AsRef::as_ref) {
+            Ok(reponse) | Err(ureq::Error::Status(_, reponse)) => {
+                rejouer_rs(&url, reponse.status(), reponse.header("DPoP-Nonce"), reponse.header("WWW-Authenticate"))
+            }
+            Err(_) => false,
+        };
+        if !rejouer || rejouee {
+            return resultat;
+        }
+        rejouee = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::{inspecter, FormatJeton};
+    use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+
+    #[test]
+    fn preuve_liee_au_jeton() {
+        let cle = CleDpop::generer();
+        let preuve = cle.preuve("GET", "https://rs.exemple.com/ressource?a=1#b", Some("LOL"));
+
+        let entete = decode_header(&preuve).unwrap();
+        assert_eq!(entete.typ.as_deref(), Some(TYPE_PREUVE));
+        let jwk = entete.jwk.unwrap();
+        let mut validation = Validation::new(Algorithm::ES256);
+        validation.set_required_spec_claims::<&str>(&[]);
+        let claims = decode::<Map<String, Value>>(&preuve, &DecodingKey::from_jwk(&jwk).unwrap(), &validation)
+            .unwrap()
+            .claims;
+        assert_eq!(claims["htm"], "GET");
+        assert_eq!(claims["htu"], "https://rs.exemple.com/ressource");
+        assert_eq!(claims["ath"], URL_SAFE_NO_PAD.encode(digest(&SHA256, b"LOL")));
+        assert_eq!(cle.jkt().len(), 43);
+    }
+
+    #[test]
+    fn nonce() {
+        assert!(nonce_exige_as(400, br#"{"error": "use_dpop_nonce"}"#));
+        // Un code d'autorisation refusé n'est pas rejoué
+        assert!(!nonce_exige_as(400, br#"{"error": "invalid_grant"}"#));
+        assert!(nonce_exige_rs(401, Some(r#"DPoP error="use_dpop_nonce", algs="ES256""#)));
+        assert!(!nonce_exige_rs(401, Some(r#"DPoP error="invalid_token""#)));
+
+        let url = "https://as.exemple.com/token";
+        assert!(!rejouer_as(url, 400, None, br#"{"error": "use_dpop_nonce"}"#));
+        assert!(rejouer_as(url, 400, Some("n2"), br#"{"error": "use_dpop_nonce"}"#));
+
+        let preuve = CleDpop::generer().preuve("POST", url, None);
+        let charge = match inspecter(&preuve) {
+            FormatJeton::Jwt { charge, .. } => charge,
+            FormatJeton::Opaque => panic!("preuve JWT"),
+        };
+        assert_eq!(charge["nonce"], "n2");
+    }
+
+    #[test]
+    fn cle_serialisee() {
+        let cle = CleDpop::generer();
+        let relue = serde_json::from_str::<CleDpop>(&serde_json::to_string(&cle).unwrap()).unwrap();
+        assert_eq!(relue.jkt(), cle.jkt());
+        assert!(!format!("{relue:?}").contains(&relue.pkcs8));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::jwt;
+use anyhow::{anyhow, Error};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use serde_json::{Map, Value};
+
+// JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)
+const CLAIMS_JWT: [&str; 4] = ["iss", "aud", "exp", "iat"];
+
+// response_mode jwt, query.jwt, fragment.jwt ou form_post.jwt
+pub fn demande(parametres: &[(String, String)]) -> bool {
+    parametres
+        .iter()
+        .any(|(k, v)| k == "response_mode" && (v == "jwt" || v.ends_with(".jwt")))
+}
+
+// JWKS de l'OP, lu avec la requête d'autorisation pour que le retour au loopback ne fasse aucun appel réseau
+pub fn jwks(document: Option<&Value>) -> Result<JwkSet, Error> {
+    let jwks_uri = document
+        .and_then(|d| d.get("jwks_uri"))
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("JARM: jwks_uri absent du document discovery"))?;
+    Ok(ureq::get(jwks_uri).call()?.into_json::<JwkSet>()?)
+}
+
+// Paramètres de la réponse d'autorisation portés par le JWT response (4.4): signature vérifiée avec le JWKS de l'OP
+// et l'algorithme de sa clé, iss est l'issuer et aud le client_id
+pub fn valider(document: Option<&Value>, jwks: Option<&JwkSet>, client_id: &str, response: &str) -> Result<Vec<(String, String)>, Error> {
+    // Une réponse chiffrée (JWE) compte cinq parties
+    if response.split('.').count() != 3 {
+        return Err(anyhow!("JARM: la réponse doit être un JWT signé"));
+    }
+    let jwks = jwks.ok_or(anyhow!("JARM: réponse JWT non demandée"))?;
+    let issuer = document
+        .and_then(|d| d.get("issuer"))
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("JARM: issuer absent du document discovery"))?;
+
+    let entete = decode_header(response)?;
+    let jwk = jwks
+        .find(entete.kid.as_deref().unwrap_or_default())
+        .ok_or(anyhow!("JARM: clé {} absente du JWKS", entete.kid.unwrap_or_default()))?;
+    let alg = jwt::algorithme(jwk, entete.alg).map_err(|e| anyhow!("JARM: {e}"))?;
+    let mut validation = Validation::new(alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "exp"]);
+    let claims = decode::<Map<String, Value>>(response, &DecodingKey::from_jwk(jwk)?, &validation)?.claims;
+
+    Ok(claims
+        .into_iter()
+        .filter(|(k, _)| !CLAIMS_JWT.contains(&k.as_str()))
+        .filter_map(|(k, v)| Some((k, v.as_str()?.to_owned())))
+        .collect())
+}
@@ -0,0 +1,128 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk};
+use jsonwebtoken::Algorithm;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+const RSA: [Algorithm; 6] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
+pub enum FormatJeton {
+    Jwt {
+        entete: Map<String, Value>,
+        charge: Map<String, Value>,
+    },
+    Opaque,
+}
+
+// Un jeton d'accès JWT (JWS compact) comporte 3 parties base64url séparées par des points.
+// Tout le reste (jeton chiffré JWE, référence, etc.) est considéré opaque.
+pub fn inspecter(jeton: &str) -> FormatJeton {
+    let parties: Vec<&str> = jeton.split('.').collect();
+    if parties.len() != 3 {
+        return FormatJeton::Opaque;
+    }
+
+    match (decoder_partie(parties[0]), decoder_partie(parties[1])) {
+        (Some(entete), Some(charge)) => FormatJeton::Jwt { entete, charge },
+        _ => FormatJeton::Opaque,
+    }
+}
+
+fn decoder_partie(partie: &str) -> Option<Map<String, Value>> {
+    let octets = URL_SAFE_NO_PAD.decode(partie.trim_end_matches('=')).ok()?;
+    match serde_json::from_slice::<Value>(&octets).ok()? {
+        Value::Object(map) => Some(map),
+        _ => None,
+    }
+}
+
+// L'algorithme de vérification est celui de la clé de l'OP, pas celui que l'entête annonce: alg du JWK s'il est présent,
+// sinon la famille de son type de clé et de sa courbe. Une entête qui ne correspond pas est refusée, tout comme une clé symétrique.
+pub fn algorithme(jwk: &Jwk, entete: Algorithm) -> Result<Algorithm, String> {
+    let admis = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+        (_, AlgorithmParameters::OctetKey(_)) => return Err("clé symétrique refusée dans le JWKS".to_owned()),
+        (Some(alg), _) => vec![Algorithm::from_str(&alg.to_string()).map_err(|_| format!("alg {alg} du JWK n'est pas un algorithme de signature"))?],
+        (None, AlgorithmParameters::RSA(_)) => RSA.to_vec(),
+        (None, AlgorithmParameters::EllipticCurve(ec)) => match ec.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            _ => Vec::new(),
+        },
+        (None, AlgorithmParameters::OctetKeyPair(okp)) if okp.curve == EllipticCurve::Ed25519 => vec![Algorithm::EdDSA],
+        (None, AlgorithmParameters::OctetKeyPair(_)) => Vec::new(),
+    };
+    if admis.contains(&entete) {
+        Ok(entete)
+    } else {
+        Err(format!(
+            "alg {entete:?} de l'entête ne correspond pas à la clé {}",
+            jwk.common.key_id.as_deref().unwrap_or_default()
+        ))
+    }
+}
+
+// Lignes Partie / Claim / Valeur pour la table de l'inspecteur des clients de bureau
+pub fn lignes(entete: &Map<String, Value>, charge: &Map<String, Value>) -> Vec<Vec<String>> {
+    std::iter::once(vec!["Partie".to_owned(), "Claim".to_owned(), "Valeur".to_owned()])
+        .chain(
+            entete
+                .iter()
+                .map(|(k, v)| vec!["entête".to_owned(), k.to_owned(), v.to_string().replace('"', "")]),
+        )
+        .chain(
+            charge
+                .iter()
+                .map(|(k, v)| vec!["charge".to_owned(), k.to_owned(), v.to_string().replace('"', "")]),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jwt() {
+        let entete = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let charge = URL_SAFE_NO_PAD.encode(r#"{"aud":"api://lol","scp":"User.Read","exp":1700000000}"#);
+        match inspecter(&format!("{entete}.{charge}.signature")) {
+            FormatJeton::Jwt { entete, charge } => {
+                assert_eq!(entete["alg"], "RS256");
+                assert_eq!(charge["scp"], "User.Read");
+            }
+            FormatJeton::Opaque => panic!("JWT attendu"),
+        }
+    }
+
+    #[test]
+    fn algorithme_de_la_cle() {
+        let jwk = |jwk: Value| serde_json::from_value::<Jwk>(jwk).unwrap();
+        let rsa = jwk(serde_json::json!({ "kty": "RSA", "kid": "r", "n": "AQAB", "e": "AQAB" }));
+        let rs256 = jwk(serde_json::json!({ "kty": "RSA", "kid": "r", "alg": "RS256", "n": "AQAB", "e": "AQAB" }));
+        let ec = jwk(serde_json::json!({ "kty": "EC", "kid": "e", "crv": "P-256", "x": "AQAB", "y": "AQAB" }));
+        let oct = jwk(serde_json::json!({ "kty": "oct", "kid": "o", "k": "TE9M" }));
+
+        assert_eq!(algorithme(&rsa, Algorithm::PS256), Ok(Algorithm::PS256));
+        assert_eq!(algorithme(&rs256, Algorithm::RS256), Ok(Algorithm::RS256));
+        assert_eq!(algorithme(&ec, Algorithm::ES256), Ok(Algorithm::ES256));
+        // L'entête ne choisit ni un autre algorithme de la clé ni une autre famille
+        assert!(algorithme(&rs256, Algorithm::PS256).is_err());
+        assert!(algorithme(&rsa, Algorithm::HS256).is_err());
+        assert!(algorithme(&ec, Algorithm::RS256).is_err());
+        assert!(algorithme(&oct, Algorithm::HS256).is_err());
+    }
+
+    #[test]
+    fn opaque() {
+        assert!(matches!(inspecter("ya29.a0AfH6SMBx"), FormatJeton::Opaque));
+        assert!(matches!(inspecter("EwBwA8l6BAAU7p9QDpi"), FormatJeton::Opaque));
+    }
+}
@@ -0,0 +1,14 @@
+#[cfg(feature = "client")]
+pub mod api;
+pub mod assertion;
+pub mod autorisation;
+#[cfg(feature = "client")]
+pub mod credentials;
+pub mod dpop;
+#[cfg(feature = "client")]
+pub mod jarm;
+pub mod jwt;
+#[cfg(feature = "client")]
+pub mod loopback;
+#[cfg(feature = "client")]
+pub mod requete;
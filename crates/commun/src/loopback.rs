@@ -0,0 +1,179 @@
+use crate::jarm;
+use anyhow::{anyhow, Error};
+use jsonwebtoken::jwk::JwkSet;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use url::{form_urlencoded, Url};
+
+// Réponse d'autorisation reçue au loopback des clients de bureau (RFC 8252): query, fragment, form_post et JARM
+
+// response_mode=fragment: le navigateur ne transmet pas le fragment, la page le poste au loopback
+const RELAIS_FRAGMENT: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><script>
+const parametres = new URLSearchParams(window.location.hash.substring(1));
+if (parametres.size > 0) {
+    fetch("/", { method: "POST", body: parametres }).then(r => r.text()).then(t => document.body.innerHTML = t);
+}
+</script></head><body></body></html>"#;
+
+// Un formulaire form_post plus volumineux est refusé (413)
+const TAILLE_CORPS: usize = 16 * 1024;
+
+#[derive(Debug)]
+struct CorpsTropVolumineux(usize);
+
+impl fmt::Display for CorpsTropVolumineux {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Corps de {} octets: la limite est de {TAILLE_CORPS} octets", self.0)
+    }
+}
+
+impl std::error::Error for CorpsTropVolumineux {}
+
+// Ce qu'il faut pour valider la réponse d'autorisation reçue au loopback
+pub struct Retour {
+    pub csrf: String,
+    pub document: Option<Value>,
+    pub jwks: Option<JwkSet>,
+    pub client_id: String,
+    pub jarm: bool,
+}
+
+// Code d'autorisation, ou l'erreur affichée à l'utilisateur
+pub type Code = Result<String, Error>;
+
+// Réponse d'autorisation reçue au loopback; None après la page relais d'un retour en fragment
+pub fn traiter(mut stream: TcpStream, retour: &Retour) -> io::Result<Option<Code>> {
+    // Sous Windows, la connexion hérite du mode non bloquant de l'écouteur
+    stream.set_nonblocking(false)?;
+    let code = match lire_requete(&stream) {
+        // Retour en fragment: la page relais reposte les paramètres
+        Ok(parametres) if parametres.is_empty() => {
+            repondre(&mut stream, "200 OK", RELAIS_FRAGMENT)?;
+            return Ok(None);
+        }
+        Ok(parametres) => reponse(parametres, retour),
+        Err(e) => Err(e),
+    };
+
+    let statut = match &code {
+        Err(e) if e.is::<CorpsTropVolumineux>() => "413 Payload Too Large",
+        _ => "200 OK",
+    };
+    let message = match &code {
+        Ok(_) => "<p>Retournez dans l'application &#128526;</p>".to_owned(),
+        Err(e) => format!("<p>{}</p>", e.to_string().replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")),
+    };
+    repondre(&mut stream, statut, &message)?;
+    Ok(Some(code))
+}
+
+// Paramètres de la query string (GET), ou du corps en formulaire pour response_mode=form_post (POST)
+fn lire_requete(stream: &TcpStream) -> Result<Vec<(String, String)>, Error> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut longueur = 0;
+    loop {
+        let mut entete = String::new();
+        if reader.read_line(&mut entete)? == 0 || entete.trim_end().is_empty() {
+            break;
+        }
+        if let Some((_, valeur)) = entete.split_once(':').filter(|(nom, _)| nom.eq_ignore_ascii_case("content-length")) {
+            longueur = valeur.trim().parse::<usize>()?;
+        }
+    }
+
+    let cible = request_line.split_whitespace().nth(1).ok_or(anyhow!("Requête invalide"))?;
+    if request_line.starts_with("POST ") {
+        if longueur > TAILLE_CORPS {
+            return Err(CorpsTropVolumineux(longueur).into());
+        }
+        let mut corps = vec![0; longueur];
+        reader.read_exact(&mut corps)?;
+        Ok(form_urlencoded::parse(&corps).into_owned().collect())
+    } else {
+        let url = Url::parse(&format!("http://localhost{cible}"))?;
+        Ok(url.query_pairs().into_owned().collect())
+    }
+}
+
+// Les paramètres d'une réponse JARM sont lus du JWT response; une réponse en clair est refusée si JARM a été demandé
+fn reponse(parametres: Vec<(String, String)>, retour: &Retour) -> Code {
+    let parametres = match parametres.iter().find(|(k, _)| k == "response") {
+        Some((_, response)) => jarm::valider(retour.document.as_ref(), retour.jwks.as_ref(), &retour.client_id, response)?,
+        None if retour.jarm => return Err(anyhow!("JARM: paramètre response absent")),
+        None => parametres,
+    };
+    let parametre = |nom: &str| parametres.iter().find(|(k, _)| k == nom).map(|(_, v)| v.as_str());
+
+    // state est vérifié avant error: une réponse d'erreur le porte aussi (RFC 6749 4.1.2.1)
+    let state = parametre("state").ok_or(anyhow!("Le jeton csrf doit être présent"))?;
+    if state != retour.csrf {
+        return Err(anyhow!("Le jeton csrf est invalide"));
+    }
+    if let Some(error) = parametre("error") {
+        return Err(anyhow!("{error}: {}", parametre("error_description").unwrap_or_default()));
+    }
+    let code = parametre("code").ok_or(anyhow!("Le code d'autorisation doit être présent"))?;
+    Ok(code.to_owned())
+}
+
+fn repondre(stream: &mut TcpStream, statut: &str, message: &str) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {statut}\r\ncontent-type: text/html; charset=utf-8\r\ncontent-length: {}\r\n\r\n{message}",
+        message.len()
+    );
+    stream.write_all(response.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn envoyer(requete: &str, jarm: bool) -> (Option<Code>, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(requete.as_bytes()).unwrap();
+        let retour = Retour {
+            csrf: "etat".to_owned(),
+            document: None,
+            jwks: None,
+            client_id: "client".to_owned(),
+            jarm,
+        };
+        let code = traiter(listener.accept().unwrap().0, &retour).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut reponse = String::new();
+        client.read_to_string(&mut reponse).unwrap();
+        (code, reponse)
+    }
+
+    #[test]
+    fn modes_de_reponse() {
+        let (code, _) = envoyer("GET /?code=abc&state=etat HTTP/1.1\r\nHost: localhost\r\n\r\n", false);
+        assert_eq!(code.unwrap().unwrap(), "abc");
+
+        let (code, reponse) = envoyer("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n", false);
+        assert!(code.is_none());
+        assert!(reponse.contains("window.location.hash"));
+
+        let corps = "code=def&state=etat";
+        let (code, _) = envoyer(&format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{corps}", corps.len()), false);
+        assert_eq!(code.unwrap().unwrap(), "def");
+
+        let (code, _) = envoyer("GET /?error=access_denied&state=lol HTTP/1.1\r\n\r\n", false);
+        assert_eq!(code.unwrap().unwrap_err().to_string(), "Le jeton csrf est invalide");
+
+        let (code, reponse) = envoyer(&format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", TAILLE_CORPS + 1), false);
+        assert!(code.unwrap().is_err());
+        assert!(reponse.starts_with("HTTP/1.1 413"));
+
+        let (code, _) = envoyer("GET /?code=abc&state=etat HTTP/1.1\r\n\r\n", true);
+        assert_eq!(code.unwrap().unwrap_err().to_string(), "JARM: paramètre response absent");
+    }
+}
@@ -0,0 +1,87 @@
+use crate::assertion::{objet_requete, parametres_assertion, AuthClient, MethodeAuth, ModePar};
+use anyhow::{anyhow, Error};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use url::Url;
+
+// Appels à l'OP des clients de bureau qui précèdent la redirection vers l'authorize endpoint; ils sont bloquants
+
+// Document discovery de l'OP; None s'il est inaccessible
+pub fn decouverte(url: &str) -> Option<Value> {
+    ureq::get(url).call().ok().and_then(|r| r.into_json::<Value>().ok())
+}
+
+// Requête d'autorisation signée (RFC 9101): le paramètre request s'ajoute aux paramètres en clair, exigés par OIDC Core 6.1.
+// aud est l'issuer de l'OP.
+pub fn signer(auth: &AuthClient, document: Option<&Value>, mut authorize_url: Url) -> Result<Url, Error> {
+    if !auth.jar {
+        return Ok(authorize_url);
+    }
+    let issuer = document
+        .and_then(|d| d.get("issuer"))
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("JAR: issuer absent du document discovery"))?;
+    let parametres = authorize_url.query_pairs().into_owned().collect::<Vec<_>>();
+    let objet = objet_requete(auth, &parametres, issuer)?;
+    authorize_url.query_pairs_mut().append_pair("request", &objet);
+    Ok(authorize_url)
+}
+
+// Pushed Authorization Request (RFC 9126) selon le mode du fournisseur:
+// les paramètres sont postés avec l'authentification du client et le navigateur ne reçoit que client_id et request_uri
+pub fn pousser(auth: &AuthClient, id: &str, secret: &str, url_token: &str, document: Option<&Value>, authorize_url: Url) -> Result<Url, Error> {
+    let endpoint = document
+        .and_then(|d| d.get("pushed_authorization_request_endpoint"))
+        .and_then(Value::as_str);
+    let endpoint = match (auth.par, endpoint) {
+        (ModePar::Jamais, _) | (ModePar::Auto, None) => return Ok(authorize_url),
+        (ModePar::Toujours, None) => return Err(anyhow!("PAR: pushed_authorization_request_endpoint absent du document discovery")),
+        (_, Some(endpoint)) => endpoint.to_owned(),
+    };
+
+    let mut formulaire = authorize_url.query_pairs().into_owned().collect::<Vec<_>>();
+    let client_id = formulaire
+        .iter()
+        .find(|(k, _)| k == "client_id")
+        .map(|(_, v)| v.clone())
+        .unwrap_or_default();
+    let mut requete = ureq::post(&endpoint);
+    match auth.methode {
+        MethodeAuth::ClientSecretPost => formulaire.push(("client_secret".to_owned(), secret.to_owned())),
+        MethodeAuth::ClientSecretBasic => {
+            let basic = STANDARD.encode(format!("{id}:{secret}"));
+            requete = requete.set("Authorization", &format!("Basic {basic}"));
+        }
+        MethodeAuth::ClientSecretJwt | MethodeAuth::PrivateKeyJwt => formulaire.extend(
+            parametres_assertion(auth, id, secret, url_token)?
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v)),
+        ),
+    }
+
+    let formulaire = formulaire.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>();
+    let reponse = match requete.send_form(&formulaire) {
+        Ok(reponse) => reponse.into_json::<Value>()?,
+        Err(ureq::Error::Status(statut, reponse)) => {
+            let erreur = reponse.into_json::<Value>().unwrap_or_default();
+            return Err(anyhow!(
+                "PAR {statut}: {} {}",
+                erreur.get("error").and_then(Value::as_str).unwrap_or_default(),
+                erreur.get("error_description").and_then(Value::as_str).unwrap_or_default()
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let request_uri = reponse
+        .get("request_uri")
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("request_uri absent de la réponse PAR"))?;
+
+    let mut url = authorize_url;
+    url.set_query(None);
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("request_uri", request_uri);
+    Ok(url)
+}
@@ -0,0 +1,37 @@
+use crate::client::{client_oauth, parametres_assertion};
+use crate::Fournisseur;
+use anyhow::Error;
+use commun::assertion::AuthClient;
+use commun::credentials::{reponse, ClientCredentials, Reponse};
+use oauth2::ureq::http_client;
+use oauth2::{Scope, TokenResponse};
+use serde_json::{Map, Value};
+
+pub fn client_credentials(f: &Fournisseur, cc: &ClientCredentials, auth: &AuthClient) -> Result<Reponse, Error> {
+    let client = client_oauth(f, auth)?;
+    let requete = client
+        .exchange_client_credentials()
+        .add_scopes(cc.scopes.split_whitespace().map(|s| Scope::new(s.to_owned())));
+    let requete = match cc.resource.trim() {
+        "" => requete,
+        resource => requete.add_extra_param("resource", resource.to_owned()),
+    };
+    let requete = parametres_assertion(f, auth)?
+        .into_iter()
+        .fold(requete, |requete, (k, v)| requete.add_extra_param(k, v));
+    let token = requete.request(http_client)?;
+
+    let mut map = Map::new();
+    map.insert("token_type".into(), Value::String(token.token_type().as_ref().to_owned()));
+    if let Some(expires_in) = token.expires_in() {
+        map.insert("expires_in".into(), Value::from(expires_in.as_secs()));
+    }
+    if let Some(scopes) = token.scopes() {
+        map.insert(
+            "scope".into(),
+            Value::String(scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")),
+        );
+    }
+
+    reponse(cc, map, token.access_token().secret())
+}
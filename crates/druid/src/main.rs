@@ -1,24 +1,40 @@
 #![windows_subsystem = "windows"]
+use commun::api::{self, Methode, ReponseApi, RequeteApi};
+use commun::assertion::{AuthClient, MethodeAuth, ModePar};
+use commun::autorisation::{Demande, SCOPES};
+use commun::credentials::{ClientCredentials, Reponse};
+use commun::jwt;
 use druid::im::Vector;
-use druid::widget::{Button, CrossAxisAlignment, Either, Flex, Image, Label, MainAxisAlignment, RadioGroup, Spinner};
+use druid::lens::Map;
+use druid::widget::{
+    Button, Checkbox, Controller, CrossAxisAlignment, Either, Flex, Image, Label, MainAxisAlignment, RadioGroup, SizedBox, Spinner, TextBox,
+};
 use druid::{
-    AppDelegate, AppLauncher, Color, Command, Data, DelegateCtx, Env, ExtEventSink, Handled, ImageBuf, Lens, Selector, Target, Widget, WidgetExt,
-    WindowDesc,
+    AppDelegate, AppLauncher, Color, Command, Data, DelegateCtx, Env, Event, EventCtx, ExtEventSink, Handled, ImageBuf, Lens, Selector, Target,
+    TimerToken, Widget, WidgetExt, WindowDesc,
 };
 use static_init::dynamic;
 
+mod client;
+mod credentials;
+mod dpop;
 mod table;
 use serde_json::value::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fmt, thread};
 use table::{Table, TableData};
 
 mod pkce;
+use credentials::client_credentials;
 use pkce::Pkce;
 
 mod seticon;
 
 const FINISH_GET_USERINFOS: Selector<Result<Vec<Vec<String>>, String>> = Selector::new("finish_get_userinfos");
+const FINISH_CLIENT_CREDENTIALS: Selector<Result<Reponse, String>> = Selector::new("finish_client_credentials");
+const FINISH_APPELER_API: Selector<Result<ReponseApi, String>> = Selector::new("finish_appeler_api");
 const ID_MS: &str = include_str!("../../../secrets/clientid.microsoft");
 const SECRET_MS: &str = include_str!("../../../secrets/secret.microsoft");
 const ID_GG: &str = include_str!("../../../secrets/clientid.google");
@@ -29,6 +45,8 @@ const TOKEN_MS: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/
 const TOKEN_GG: &str = "https://oauth2.googleapis.com/token";
 const INFOS_MS: &str = "https://graph.microsoft.com/oidc/userinfo";
 const INFOS_GG: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+const DISCOVERY_MS: &str = "https://login.microsoftonline.com/consumers/v2.0/.well-known/openid-configuration";
+const DISCOVERY_GG: &str = "https://accounts.google.com/.well-known/openid-configuration";
 
 #[dynamic]
 static mut TOKEN: Option<(Fournisseur, Pkce)> = None;
@@ -37,12 +55,73 @@ static mut TOKEN: Option<(Fournisseur, Pkce)> = None;
 struct AppData {
     radio_fournisseur: Fournisseur,
     label_fournisseur: String,
+    mode: Mode,
+    // Scopes cochés, autres scopes et paramètres additionnels de la requête d'autorisation
+    scopes: [bool; SCOPES.len()],
+    autres_scopes: String,
+    parametres: String,
+    // Authentification au token endpoint choisie pour chaque fournisseur
+    #[data(eq)]
+    auth_clients: HashMap<Fournisseur, AuthClient>,
+    #[data(eq)]
+    client_credentials: ClientCredentials,
+    #[data(eq)]
+    requete_api: RequeteApi,
+    titre_infos: String,
     infos: Arc<TableData>,
+    // Réponse de la ressource protégée appelée avec le jeton client_credentials ou entêtes de la réponse de l'API
+    ressource: Arc<TableData>,
+    // Inspection du jeton d'accès; expiration en secondes depuis l'époque Unix
+    titre_jeton: String,
+    jeton: Arc<TableData>,
+    expiration: Option<u64>,
+    compteur: String,
+    // Liaison DPoP: cnf.jkt du jeton comparé à l'empreinte de la clé
+    liaison: String,
     en_traitement: bool,
     erreur: String,
 }
 
-#[derive(Clone, PartialEq, Data)]
+impl AppData {
+    fn auth(&self) -> AuthClient {
+        self.auth_clients.get(&self.radio_fournisseur).cloned().unwrap_or_default()
+    }
+
+    fn auth_mut(&mut self) -> &mut AuthClient {
+        self.auth_clients.entry(self.radio_fournisseur.clone()).or_default()
+    }
+}
+
+// Les énumérations de commun n'implémentent pas Data: elles sont comparées par égalité
+#[derive(Clone, Copy, PartialEq)]
+struct Choix<T>(T);
+
+impl<T: Clone + PartialEq + 'static> Data for Choix<T> {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+// Code d'autorisation (PKCE), grant client_credentials ou explorateur d'API avec le jeton du code d'autorisation
+#[derive(Clone, Copy, PartialEq, Data)]
+enum Mode {
+    Code,
+    ClientCredentials,
+    Api,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            Mode::Code => "Code d'autorisation",
+            Mode::ClientCredentials => "Client credentials",
+            Mode::Api => "API",
+        };
+        f.write_str(mode)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Data)]
 pub enum Fournisseur {
     Microsoft,
     Google,
@@ -79,30 +158,41 @@ impl Fournisseur {
             Self::Google => INFOS_GG,
         }
     }
+
+    fn discovery(&self) -> &str {
+        match self {
+            Self::Microsoft => DISCOVERY_MS,
+            Self::Google => DISCOVERY_GG,
+        }
+    }
 }
 
-fn request_userinfos(f: &Fournisseur) -> Result<Value, anyhow::Error> {
+// Le jeton est réutilisé tant qu'il n'est pas expiré et que la demande est la même
+fn jeton(f: &Fournisseur, demande: &Demande, auth: &AuthClient) -> Result<(), anyhow::Error> {
     let token = TOKEN.read();
     if token.is_some() {
         let (fournisseur, secret) = token.as_ref().unwrap();
-        if f != fournisseur || secret.is_expired() {
+        if f != fournisseur || secret.is_expired() || secret.demande() != demande {
             drop(token);
-            TOKEN.write().replace((f.to_owned(), Pkce::new(f)?));
+            TOKEN.write().replace((f.to_owned(), Pkce::new(f, demande, auth)?));
         }
     } else {
         drop(token);
-        TOKEN.write().replace((f.to_owned(), Pkce::new(f)?));
+        TOKEN.write().replace((f.to_owned(), Pkce::new(f, demande, auth)?));
     }
+    Ok(())
+}
 
-    Ok(ureq::get(f.userinfos())
-        .set("Authorization", &format!("Bearer {}", TOKEN.read().as_ref().unwrap().1.secret()))
-        .call()?
-        .into_json::<Value>()?)
+fn request_userinfos(f: &Fournisseur, demande: &Demande, auth: &AuthClient) -> Result<Value, anyhow::Error> {
+    jeton(f, demande, auth)?;
+    let token = TOKEN.read();
+    let pkce = &token.as_ref().expect("jeton obtenu").1;
+    Ok(dpop::envoyer(|| ureq::get(f.userinfos()), pkce.secret(), pkce.dpop(), None)?.into_json::<Value>()?)
 }
 
-fn get_userinfos(sink: ExtEventSink, fournisseur: Fournisseur) {
+fn get_userinfos(sink: ExtEventSink, fournisseur: Fournisseur, demande: Demande, auth: AuthClient) {
     thread::spawn(move || {
-        let result = match request_userinfos(&fournisseur) {
+        let result = match request_userinfos(&fournisseur, &demande, &auth) {
             Ok(value) => match value {
                 Value::Object(map) => {
                     let table: Vec<Vec<String>> = map.iter().map(|(k, v)| vec![k.to_owned(), v.to_string().replace('"', "")]).collect();
@@ -118,17 +208,139 @@ fn get_userinfos(sink: ExtEventSink, fournisseur: Fournisseur) {
     });
 }
 
+fn get_client_credentials(sink: ExtEventSink, fournisseur: Fournisseur, cc: ClientCredentials, auth: AuthClient) {
+    thread::spawn(move || {
+        let result = client_credentials(&fournisseur, &cc, &auth).map_err(|e| e.to_string());
+        sink.submit_command(FINISH_CLIENT_CREDENTIALS, result, Target::Auto)
+            .expect("command failed to submit");
+    });
+}
+
+fn appeler_api(sink: ExtEventSink, fournisseur: Fournisseur, demande: Demande, auth: AuthClient, requete: RequeteApi) {
+    thread::spawn(move || {
+        let result = jeton(&fournisseur, &demande, &auth)
+            .and_then(|_| {
+                let token = TOKEN.read();
+                let pkce = &token.as_ref().expect("jeton obtenu").1;
+                api::appeler(&requete, pkce.secret(), pkce.dpop())
+            })
+            .map_err(|e| e.to_string());
+        sink.submit_command(FINISH_APPELER_API, result, Target::Auto)
+            .expect("command failed to submit");
+    });
+}
+
+fn demande(data: &AppData) -> Demande {
+    let scopes = SCOPES
+        .iter()
+        .zip(data.scopes)
+        .filter(|&(_, coche)| coche)
+        .map(|(scope, _)| scope.to_string());
+    Demande::saisie(scopes, &data.autres_scopes, &data.parametres)
+}
+
+fn maintenant() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// exp fait foi pour un JWT, sinon la durée de vie retournée avec le jeton
+fn inspecter(data: &mut AppData) {
+    let token = TOKEN.read();
+    let Some((_, pkce)) = token.as_ref() else {
+        data.erreur = "Aucun jeton: cliquez d'abord sur UserInfos".to_owned();
+        return;
+    };
+    let expiration = maintenant() + pkce.expire_dans().as_secs();
+    match jwt::inspecter(pkce.secret()) {
+        jwt::FormatJeton::Jwt { entete, charge } => {
+            data.titre_jeton = "Jeton d'accès JWT".to_owned();
+            data.expiration = Some(charge.get("exp").and_then(Value::as_u64).unwrap_or(expiration));
+            data.liaison = pkce.dpop().map(|cle| dpop::liaison(cle, Some(&charge))).unwrap_or_default();
+            data.jeton = Arc::new(jwt::lignes(&entete, &charge).into());
+        }
+        jwt::FormatJeton::Opaque => {
+            data.titre_jeton = "Jeton d'accès opaque: il ne peut pas être décodé par le client".to_owned();
+            data.expiration = Some(expiration);
+            data.liaison = pkce.dpop().map(|cle| dpop::liaison(cle, None)).unwrap_or_default();
+            data.jeton = Arc::new(TableData::default());
+        }
+    }
+    data.compteur = compteur(data.expiration);
+}
+
+fn compteur(expiration: Option<u64>) -> String {
+    match expiration.map(|exp| exp.saturating_sub(maintenant())) {
+        Some(0) => "Expiré".to_owned(),
+        Some(restant) => format!("Expire dans {}:{:02}", restant / 60, restant % 60),
+        None => String::new(),
+    }
+}
+
+// Le compteur d'expiration du jeton inspecté est mis à jour chaque seconde
+struct Minuterie {
+    jeton: TimerToken,
+}
+
+impl<W: Widget<AppData>> Controller<AppData, W> for Minuterie {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppData, env: &Env) {
+        match event {
+            Event::WindowConnected => self.jeton = ctx.request_timer(Duration::from_secs(1)),
+            Event::Timer(jeton) if *jeton == self.jeton => {
+                data.compteur = compteur(data.expiration);
+                self.jeton = ctx.request_timer(Duration::from_secs(1));
+            }
+            _ => (),
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
 struct Delegate;
 
 impl AppDelegate<AppData> for Delegate {
     fn command(&mut self, _ctx: &mut DelegateCtx, _target: Target, cmd: &Command, data: &mut AppData, _env: &Env) -> Handled {
+        // Le corps de la réponse va dans la table des infos, ses entêtes dans celle de la ressource
+        if let Some(resultat) = cmd.get(FINISH_APPELER_API) {
+            data.en_traitement = false;
+            match resultat {
+                Ok(reponse) => {
+                    data.titre_infos = format!("API {}: statut {}", data.label_fournisseur, reponse.statut);
+                    data.infos = Arc::new(reponse.corps.clone().into());
+                    data.ressource = Arc::new(reponse.entetes.clone().into());
+                }
+                Err(e) => data.erreur = e.clone(),
+            }
+            return Handled::Yes;
+        }
+
+        if let Some(resultat) = cmd.get(FINISH_CLIENT_CREDENTIALS) {
+            data.en_traitement = false;
+            match resultat {
+                Ok((jeton, ressource)) => {
+                    data.titre_infos = format!("Client credentials {}", data.label_fournisseur);
+                    data.infos = Arc::new(jeton.clone().into());
+                    data.ressource = Arc::new(ressource.clone().map(TableData::from).unwrap_or_default());
+                }
+                Err(e) => data.erreur = e.clone(),
+            }
+            return Handled::Yes;
+        }
+
         match cmd.get(FINISH_GET_USERINFOS) {
             Some(Ok(infos)) => {
                 data.en_traitement = false;
+                data.titre_infos = format!("UserInfos {}", data.label_fournisseur);
+                data.ressource = Arc::new(TableData::default());
                 data.infos = Arc::new(TableData {
                     rows: infos.to_owned(),
                     header: vec!["Propriété".to_owned(), "Valeur".to_owned()],
                 });
+                // Le jeton a pu être renouvelé
+                data.titre_jeton = String::new();
+                data.jeton = Arc::new(TableData::default());
+                data.expiration = None;
+                data.compteur = String::new();
+                data.liaison = String::new();
                 Handled::Yes
             }
             Some(Err(e)) => {
@@ -160,23 +372,225 @@ fn ui_builder() -> impl Widget<AppData> {
     oidc.add_child(RadioGroup::row(fournisseurs).lens(AppData::radio_fournisseur));
     oidc.add_default_spacer();
 
+    oidc.add_child(Label::new("Mode:"));
+    oidc.add_default_spacer();
+    oidc.add_child(RadioGroup::row([Mode::Code, Mode::ClientCredentials, Mode::Api].map(|mode| (mode.to_string(), mode))).lens(AppData::mode));
+    oidc.add_default_spacer();
+
+    let mut code = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    code.add_child(Label::new("Scopes:"));
+    code.add_default_spacer();
+    let mut scopes = Flex::row();
+    for (i, scope) in SCOPES.iter().enumerate() {
+        let coche = Map::new(
+            move |data: &AppData| data.scopes[i],
+            move |data: &mut AppData, coche| data.scopes[i] = coche,
+        );
+        scopes.add_child(Checkbox::new(*scope).lens(coche));
+        scopes.add_default_spacer();
+    }
+    code.add_child(scopes);
+    code.add_default_spacer();
+    code.add_child(
+        TextBox::new()
+            .with_placeholder("Autres scopes")
+            .fix_width(300.)
+            .lens(AppData::autres_scopes),
+    );
+    code.add_default_spacer();
+    code.add_child(Label::new("Paramètres:"));
+    code.add_default_spacer();
+    code.add_child(
+        TextBox::new()
+            .with_placeholder("prompt=consent&login_hint=...")
+            .fix_width(300.)
+            .lens(AppData::parametres),
+    );
+    code.add_default_spacer();
+
+    let jar = Map::new(|data: &AppData| data.auth().jar, |data: &mut AppData, jar| data.auth_mut().jar = jar);
+    code.add_child(Checkbox::new("Requête d'autorisation signée (JAR)").lens(jar));
+    code.add_default_spacer();
+    let dpop = Map::new(|data: &AppData| data.auth().dpop, |data: &mut AppData, dpop| data.auth_mut().dpop = dpop);
+    code.add_child(Checkbox::new("Jeton lié par DPoP").lens(dpop));
+    code.add_default_spacer();
+    code.add_child(Label::new("Pushed Authorization Request:"));
+    code.add_default_spacer();
+    let par = Map::new(
+        |data: &AppData| Choix(data.auth().par),
+        |data: &mut AppData, par: Choix<ModePar>| data.auth_mut().par = par.0,
+    );
+    code.add_child(RadioGroup::row(ModePar::ALL.map(|par| (par.to_string(), Choix(par)))).lens(par));
+    code.add_default_spacer();
+
+    let cc_scopes = Map::new(
+        |data: &AppData| data.client_credentials.scopes.clone(),
+        |data: &mut AppData, scopes| data.client_credentials.scopes = scopes,
+    );
+    let cc_resource = Map::new(
+        |data: &AppData| data.client_credentials.resource.clone(),
+        |data: &mut AppData, resource| data.client_credentials.resource = resource,
+    );
+    let cc_url = Map::new(
+        |data: &AppData| data.client_credentials.url.clone(),
+        |data: &mut AppData, url| data.client_credentials.url = url,
+    );
+    let client_credentials = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Scopes:"))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_placeholder("https://graph.microsoft.com/.default")
+                .fix_width(300.)
+                .lens(cc_scopes),
+        )
+        .with_default_spacer()
+        .with_child(Label::new("Resource:"))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_placeholder("https://api.exemple.com")
+                .fix_width(300.)
+                .lens(cc_resource),
+        )
+        .with_default_spacer()
+        .with_child(Label::new("Ressource protégée (GET):"))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_placeholder("https://graph.microsoft.com/v1.0/users")
+                .fix_width(300.)
+                .lens(cc_url),
+        )
+        .with_default_spacer();
+    let url_api = Map::new(
+        |data: &AppData| data.requete_api.url.clone(),
+        |data: &mut AppData, url| data.requete_api.url = url,
+    );
+    let methode_api = Map::new(
+        |data: &AppData| Choix(data.requete_api.methode),
+        |data: &mut AppData, methode: Choix<Methode>| data.requete_api.methode = methode.0,
+    );
+    let entetes_api = Map::new(
+        |data: &AppData| data.requete_api.entetes.clone(),
+        |data: &mut AppData, entetes| data.requete_api.entetes = entetes,
+    );
+    let corps_api = Map::new(
+        |data: &AppData| data.requete_api.corps.clone(),
+        |data: &mut AppData, corps| data.requete_api.corps = corps,
+    );
+    let api = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Requête:"))
+        .with_default_spacer()
+        .with_child(RadioGroup::row(Methode::ALL.map(|methode| (methode.to_string(), Choix(methode)))).lens(methode_api))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_placeholder("https://graph.microsoft.com/v1.0/me")
+                .fix_width(300.)
+                .lens(url_api),
+        )
+        .with_default_spacer()
+        .with_child(Label::new("Entêtes (Nom: valeur):"))
+        .with_default_spacer()
+        .with_child(TextBox::multiline().fix_size(300., 60.).lens(entetes_api))
+        .with_default_spacer()
+        .with_child(Label::new("Corps:"))
+        .with_default_spacer()
+        .with_child(TextBox::multiline().fix_size(300., 80.).lens(corps_api))
+        .with_default_spacer();
+    oidc.add_child(Either::new(
+        |data: &AppData, _env| data.mode == Mode::Code,
+        code,
+        Either::new(|data: &AppData, _env| data.mode == Mode::ClientCredentials, client_credentials, api),
+    ));
+
+    oidc.add_child(Label::new("Authentification au token endpoint:"));
+    oidc.add_default_spacer();
+    let methode = Map::new(
+        |data: &AppData| Choix(data.auth().methode),
+        |data: &mut AppData, methode: Choix<MethodeAuth>| data.auth_mut().methode = methode.0,
+    );
+    oidc.add_child(RadioGroup::column(MethodeAuth::ALL.map(|methode| (methode.to_string(), Choix(methode)))).lens(methode));
+    let cle = Map::new(|data: &AppData| data.auth().cle, |data: &mut AppData, cle| data.auth_mut().cle = cle);
+    oidc.add_child(Either::new(
+        |data: &AppData, _env| {
+            let auth = data.auth();
+            auth.methode == MethodeAuth::PrivateKeyJwt || (data.mode == Mode::Code && auth.jar)
+        },
+        TextBox::new()
+            .with_placeholder("Clé privée PEM (RSA ou EC P-256)")
+            .fix_width(300.)
+            .lens(cle),
+        SizedBox::empty(),
+    ));
+    oidc.add_default_spacer();
+
     let bouton = Button::new("UserInfos")
         .on_click(|ctx, data: &mut AppData, _| {
             data.erreur = String::new();
             data.label_fournisseur = data.radio_fournisseur.to_string();
             data.en_traitement = true;
-            get_userinfos(ctx.get_external_handle(), data.radio_fournisseur.clone());
+            get_userinfos(ctx.get_external_handle(), data.radio_fournisseur.clone(), demande(data), data.auth());
         })
         .fix_height(30.0);
 
-    oidc.add_child(Either::new(|data, _env| data.en_traitement, Spinner::new(), bouton));
+    let bouton_jeton = Button::new("Jeton")
+        .on_click(|_, data: &mut AppData, _| {
+            data.erreur = String::new();
+            inspecter(data);
+        })
+        .fix_height(30.0);
+
+    let bouton_cc = Button::new("Client credentials")
+        .on_click(|ctx, data: &mut AppData, _| {
+            data.erreur = String::new();
+            data.label_fournisseur = data.radio_fournisseur.to_string();
+            data.en_traitement = true;
+            get_client_credentials(
+                ctx.get_external_handle(),
+                data.radio_fournisseur.clone(),
+                data.client_credentials.clone(),
+                data.auth(),
+            );
+        })
+        .fix_height(30.0);
+
+    let bouton_api = Button::new("Envoyer")
+        .on_click(|ctx, data: &mut AppData, _| {
+            data.erreur = String::new();
+            data.label_fournisseur = data.radio_fournisseur.to_string();
+            data.en_traitement = true;
+            let requete = RequeteApi {
+                corps: data.requete_api.corps.trim_end().to_owned(),
+                ..data.requete_api.clone()
+            };
+            appeler_api(
+                ctx.get_external_handle(),
+                data.radio_fournisseur.clone(),
+                demande(data),
+                data.auth(),
+                requete,
+            );
+        })
+        .disabled_if(|data: &AppData, _env| data.requete_api.url.trim().is_empty())
+        .fix_height(30.0);
+
+    let boutons = Either::new(
+        |data: &AppData, _env| data.mode == Mode::Code,
+        Flex::row().with_child(bouton).with_default_spacer().with_child(bouton_jeton),
+        Either::new(|data: &AppData, _env| data.mode == Mode::ClientCredentials, bouton_cc, bouton_api),
+    );
+    oidc.add_child(Either::new(|data, _env| data.en_traitement, Spinner::new(), boutons));
 
     let infos = Flex::column()
         .must_fill_main_axis(true)
         .cross_axis_alignment(CrossAxisAlignment::Start)
         .main_axis_alignment(MainAxisAlignment::Center)
         .with_child(
-            Label::new(|data: &AppData, _env: &_| format!("UserInfos {}", data.label_fournisseur))
+            Label::new(|data: &AppData, _env: &_| data.titre_infos.clone())
                 .with_text_size(18.)
                 .with_text_color(Color::from_hex_str("FFA500").unwrap()),
         )
@@ -185,6 +599,25 @@ fn ui_builder() -> impl Widget<AppData> {
             Table::new()
                 .with_header_text_color(Color::from_hex_str("FFA500").unwrap())
                 .lens(AppData::infos),
+        )
+        .with_default_spacer()
+        .with_child(
+            Table::new()
+                .with_header_text_color(Color::from_hex_str("FFA500").unwrap())
+                .lens(AppData::ressource),
+        )
+        .with_default_spacer()
+        .with_child(
+            Label::new(|data: &AppData, _env: &_| data.titre_jeton.clone())
+                .with_text_size(18.)
+                .with_text_color(Color::from_hex_str("FFA500").unwrap()),
+        )
+        .with_child(Label::new(|data: &AppData, _env: &_| data.compteur.clone()).controller(Minuterie { jeton: TimerToken::INVALID }))
+        .with_child(Label::new(|data: &AppData, _env: &_| data.liaison.clone()))
+        .with_child(
+            Table::new()
+                .with_header_text_color(Color::from_hex_str("FFA500").unwrap())
+                .lens(AppData::jeton),
         );
 
     let main = Flex::row().with_default_spacer().with_child(oidc).with_spacer(40.).with_child(infos);
@@ -208,7 +641,21 @@ pub fn main() {
     let data = AppData {
         radio_fournisseur: Fournisseur::Microsoft,
         label_fournisseur: String::new(),
+        mode: Mode::Code,
+        scopes: [true, true, true, false],
+        autres_scopes: String::new(),
+        parametres: String::new(),
+        auth_clients: HashMap::new(),
+        client_credentials: ClientCredentials::default(),
+        requete_api: RequeteApi::default(),
+        titre_infos: String::new(),
         infos: Arc::new(TableData::default()),
+        ressource: Arc::new(TableData::default()),
+        titre_jeton: String::new(),
+        jeton: Arc::new(TableData::default()),
+        expiration: None,
+        compteur: String::new(),
+        liaison: String::new(),
         en_traitement: false,
         erreur: String::new(),
     };
@@ -22,6 +22,14 @@ impl Default for TableData {
     }
 }
 
+// La première ligne est l'entête
+impl From<Vec<Vec<String>>> for TableData {
+    fn from(mut lignes: Vec<Vec<String>>) -> Self {
+        let header = if lignes.is_empty() { Vec::new() } else { lignes.remove(0) };
+        TableData { header, rows: lignes }
+    }
+}
+
 // Find out the maximum layout width of each column
 fn layout_columns_width(ctx: &mut UpdateCtx, data: &Arc<TableData>, env: &Env) -> Option<Vec<f64>> {
     let mut columns_width = Vec::new();
@@ -0,0 +1,32 @@
+use crate::Fournisseur;
+use anyhow::Error;
+use commun::assertion::{self, AuthClient, MethodeAuth};
+use oauth2::basic::BasicClient;
+use oauth2::{AuthType, AuthUrl, ClientId, ClientSecret, EndpointNotSet, EndpointSet, TokenUrl};
+
+pub type Client = BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
+
+// Pour les méthodes JWT, le secret n'est pas transmis: l'assertion le remplace
+pub fn client_oauth(f: &Fournisseur, auth: &AuthClient) -> Result<Client, Error> {
+    let (id, secret) = f.secrets();
+    let (url_auth, url_token) = f.endpoints();
+
+    let client = BasicClient::new(ClientId::new(id.to_owned()))
+        .set_auth_uri(AuthUrl::new(url_auth.to_owned())?)
+        .set_token_uri(TokenUrl::new(url_token.to_owned())?);
+
+    let secret = ClientSecret::new(secret.to_owned());
+    let client = match auth.methode {
+        MethodeAuth::ClientSecretPost => client.set_client_secret(secret).set_auth_type(AuthType::RequestBody),
+        MethodeAuth::ClientSecretBasic => client.set_client_secret(secret).set_auth_type(AuthType::BasicAuth),
+        MethodeAuth::ClientSecretJwt | MethodeAuth::PrivateKeyJwt => client.set_auth_type(AuthType::RequestBody),
+    };
+
+    Ok(client)
+}
+
+pub fn parametres_assertion(f: &Fournisseur, auth: &AuthClient) -> Result<Vec<(&'static str, String)>, Error> {
+    let (id, secret) = f.secrets();
+    let (_, url_token) = f.endpoints();
+    assertion::parametres_assertion(auth, id, secret, url_token)
+}
@@ -0,0 +1,48 @@
+use commun::dpop;
+use oauth2::{HttpClientError, HttpRequest, HttpResponse, SyncHttpClient};
+use std::io::Read;
+
+// OAuth 2.0 Demonstrating Proof of Possession (RFC 9449): preuves et nonces dans commun::dpop
+pub use commun::dpop::{envoyer, liaison, CleDpop};
+
+// Client HTTP du token endpoint: la requête porte une preuve DPoP si une clé est fournie.
+// Les réponses d'erreur sont transmises avec leurs entêtes pour lire DPoP-Nonce.
+pub fn requete_jeton(cle: Option<&CleDpop>) -> impl Fn(HttpRequest) -> Result<HttpResponse, HttpClientError<ureq::Error>> + '_ {
+    move |requete: HttpRequest| {
+        let Some(cle) = cle else {
+            return ureq::agent().call(requete);
+        };
+
+        let url = requete.uri().to_string();
+        let mut rejouee = false;
+        loop {
+            let appel = requete
+                .headers()
+                .iter()
+                .filter_map(|(nom, valeur)| Some((nom.as_str(), valeur.to_str().ok()?)))
+                .fold(ureq::request(requete.method().as_str(), &url), |appel, (nom, valeur)| {
+                    appel.set(nom, valeur)
+                })
+                .set("DPoP", &cle.preuve(requete.method().as_str(), &url, None));
+            let reponse = match appel.send_bytes(requete.body()) {
+                Ok(reponse) | Err(ureq::Error::Status(_, reponse)) => reponse,
+                Err(e) => return Err(HttpClientError::Reqwest(Box::new(e))),
+            };
+
+            let statut = reponse.status();
+            let nonce = reponse.header("DPoP-Nonce").map(str::to_owned);
+            let mut builder = oauth2::http::Response::builder().status(statut);
+            for nom in reponse.headers_names() {
+                if let Some(valeur) = reponse.header(&nom) {
+                    builder = builder.header(nom.as_str(), valeur);
+                }
+            }
+            let mut corps = Vec::new();
+            reponse.into_reader().read_to_end(&mut corps)?;
+            if rejouee || !dpop::rejouer_as(&url, statut, nonce.as_deref(), &corps) {
+                return builder.body(corps).map_err(HttpClientError::Http);
+            }
+            rejouee = true;
+        }
+    }
+}
@@ -1,18 +1,29 @@
 #![windows_subsystem = "windows"]
 use anyhow::{anyhow, Result};
+use commun::assertion::{AuthClient, MethodeAuth, ModePar};
+use commun::autorisation::{Demande, SCOPES};
+use commun::jwt;
 use cosmic_time::{anim, chain, id, Duration, Exponential, Instant, Timeline};
 use iced::advanced::image::Handle;
-use iced::widget::{button, column, container, radio, row, text, Image};
+use iced::widget::{button, checkbox, column, container, pick_list, radio, row, text, text_editor, text_input, Image};
 use iced::window::icon;
 use iced::{application, Color, Element, Padding, Subscription, Task, Theme};
 use iced::{window, Event, Renderer};
 use mode_couleur::{stream_event_mode_couleur, ModeCouleur};
 use serde_json::value::Value;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fmt, iter};
 use table::Table;
 
+mod client;
+mod credentials;
+mod dpop;
 mod pkce;
 mod table;
+use commun::api::{self, Methode, ReponseApi, RequeteApi};
+use commun::credentials::{ClientCredentials, Reponse};
+use credentials::client_credentials;
 use pkce::Pkce;
 
 #[cfg_attr(target_os = "linux", path = "nix_mode_couleur.rs")]
@@ -27,11 +38,12 @@ const AUTH_MS: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/a
 const AUTH_GG: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 const TOKEN_MS: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/token";
 const TOKEN_GG: &str = "https://oauth2.googleapis.com/token";
+const DISCOVERY_MS: &str = "https://login.microsoftonline.com/consumers/v2.0/.well-known/openid-configuration";
+const DISCOVERY_GG: &str = "https://accounts.google.com/.well-known/openid-configuration";
 const INFOS_MS: &str = "https://graph.microsoft.com/oidc/userinfo";
 const INFOS_GG: &str = "https://openidconnect.googleapis.com/v1/userinfo";
 const ICON: &[u8; 1612] = include_bytes!("../openid.png");
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Fournisseur {
     Microsoft,
     Google,
@@ -47,6 +59,24 @@ impl fmt::Display for Fournisseur {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Code,
+    ClientCredentials,
+    Api,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            Mode::Code => "Code d'autorisation",
+            Mode::ClientCredentials => "Client credentials",
+            Mode::Api => "API",
+        };
+        f.write_str(mode)
+    }
+}
+
 impl Fournisseur {
     fn endpoints(&self) -> (&str, &str) {
         match self {
@@ -62,6 +92,13 @@ impl Fournisseur {
         }
     }
 
+    fn discovery(&self) -> &str {
+        match self {
+            Self::Microsoft => DISCOVERY_MS,
+            Self::Google => DISCOVERY_GG,
+        }
+    }
+
     fn userinfos(&self) -> &str {
         match self {
             Self::Microsoft => INFOS_MS,
@@ -73,16 +110,45 @@ impl Fournisseur {
 #[derive(Debug, Clone)]
 enum Message {
     FournisseurChanged(Fournisseur),
+    ModeChanged(Mode),
+    CcScopesChanged(String),
+    CcResourceChanged(String),
+    CcUrlChanged(String),
+    GetClientCredentials,
+    ClientCredentials(Result<Reponse, String>),
+    ScopeChanged(usize, bool),
+    AutresScopesChanged(String),
+    ParametresChanged(String),
+    MethodeAuthChanged(MethodeAuth),
+    CleChanged(String),
+    JarChanged(bool),
+    ParChanged(ModePar),
+    DpopChanged(bool),
+    MethodeApiChanged(Methode),
+    UrlApiChanged(String),
+    EntetesApi(text_editor::Action),
+    CorpsApi(text_editor::Action),
+    AppelerApi,
+    ReponseApi(Result<(ReponseApi, Pkce), String>),
     GetInfos,
     Infos(Result<(Option<Vec<Vec<String>>>, Option<Pkce>), String>),
     ModeCouleurChanged(Result<ModeCouleur, String>),
     Tick(Instant),
+    Inspecter,
+    Seconde,
+}
+
+#[derive(Debug, Clone)]
+// La liaison DPoP est indiquée pour un jeton obtenu avec une preuve
+enum Inspection {
+    Jwt(Vec<Vec<String>>, Option<u64>, Option<String>),
+    Opaque(Option<String>),
 }
 
 fn main() -> iced::Result {
     let icon = icon::from_file_data(ICON, None).unwrap();
     let window = window::Settings {
-        size: iced_core::Size { width: 900.0, height: 380.0 },
+        size: iced_core::Size { width: 900.0, height: 600.0 },
         icon: Some(icon),
         ..Default::default()
     };
@@ -97,8 +163,20 @@ fn main() -> iced::Result {
 struct App {
     radio_fournisseur: Fournisseur,
     fournisseur: String,
+    mode: Mode,
+    client_credentials: ClientCredentials,
+    ressource: Option<Vec<Vec<String>>>,
+    requete_api: RequeteApi,
+    entetes_api: text_editor::Content,
+    corps_api: text_editor::Content,
+    reponse_api: Option<ReponseApi>,
+    scopes: [bool; SCOPES.len()],
+    autres_scopes: String,
+    parametres: String,
+    auth_clients: HashMap<Fournisseur, AuthClient>,
     secret: Option<Pkce>,
     infos: Option<Vec<Vec<String>>>,
+    inspection: Option<Inspection>,
     en_traitement: bool,
     erreur: String,
     theme: Theme,
@@ -112,8 +190,20 @@ impl App {
             Self {
                 radio_fournisseur: Fournisseur::Microsoft,
                 fournisseur: String::new(),
+                mode: Mode::Code,
+                client_credentials: ClientCredentials::default(),
+                ressource: None,
+                requete_api: RequeteApi::default(),
+                entetes_api: text_editor::Content::new(),
+                corps_api: text_editor::Content::new(),
+                reponse_api: None,
+                scopes: [true, true, true, false],
+                autres_scopes: String::new(),
+                parametres: String::new(),
+                auth_clients: HashMap::new(),
                 secret: None,
                 infos: None,
+                inspection: None,
                 en_traitement: false,
                 erreur: String::new(),
                 theme: Theme::Light,
@@ -134,16 +224,129 @@ impl App {
                 self.radio_fournisseur = fournisseur;
                 Task::none()
             }
-            Message::GetInfos => {
-                let fournisseur = self.radio_fournisseur.to_string();
-                if self.fournisseur != fournisseur {
-                    self.secret = None;
-                    self.fournisseur = fournisseur;
+            Message::ModeChanged(mode) => {
+                self.mode = mode;
+                self.infos = None;
+                self.ressource = None;
+                self.reponse_api = None;
+                self.inspection = None;
+                Task::none()
+            }
+            Message::CcScopesChanged(scopes) => {
+                self.client_credentials.scopes = scopes;
+                Task::none()
+            }
+            Message::CcResourceChanged(resource) => {
+                self.client_credentials.resource = resource;
+                Task::none()
+            }
+            Message::CcUrlChanged(url) => {
+                self.client_credentials.url = url;
+                Task::none()
+            }
+            Message::GetClientCredentials => {
+                self.fournisseur = self.radio_fournisseur.to_string();
+                let auth = self.auth_clients.get(&self.radio_fournisseur).cloned().unwrap_or_default();
+                let task = client_credentials(self.radio_fournisseur, self.client_credentials.clone(), auth);
+                self.infos = None;
+                self.ressource = None;
+                self.erreur = String::new();
+                self.en_traitement = true;
+                Task::perform(task, |r| Message::ClientCredentials(r.map_err(|e| format!("{e:#}"))))
+            }
+            Message::ClientCredentials(result) => {
+                match result {
+                    Ok((jeton, ressource)) => {
+                        self.infos = Some(jeton);
+                        self.ressource = ressource;
+                    }
+                    Err(e) => self.erreur = e,
+                }
+                self.en_traitement = false;
+                Task::none()
+            }
+            Message::ScopeChanged(i, coche) => {
+                self.scopes[i] = coche;
+                Task::none()
+            }
+            Message::AutresScopesChanged(scopes) => {
+                self.autres_scopes = scopes;
+                Task::none()
+            }
+            Message::ParametresChanged(parametres) => {
+                self.parametres = parametres;
+                Task::none()
+            }
+            Message::MethodeAuthChanged(methode) => {
+                self.auth_clients.entry(self.radio_fournisseur).or_default().methode = methode;
+                Task::none()
+            }
+            Message::CleChanged(cle) => {
+                self.auth_clients.entry(self.radio_fournisseur).or_default().cle = cle;
+                Task::none()
+            }
+            Message::JarChanged(jar) => {
+                self.auth_clients.entry(self.radio_fournisseur).or_default().jar = jar;
+                Task::none()
+            }
+            Message::ParChanged(par) => {
+                self.auth_clients.entry(self.radio_fournisseur).or_default().par = par;
+                Task::none()
+            }
+            Message::DpopChanged(dpop) => {
+                self.auth_clients.entry(self.radio_fournisseur).or_default().dpop = dpop;
+                Task::none()
+            }
+            Message::MethodeApiChanged(methode) => {
+                self.requete_api.methode = methode;
+                Task::none()
+            }
+            Message::UrlApiChanged(url) => {
+                self.requete_api.url = url;
+                Task::none()
+            }
+            Message::EntetesApi(action) => {
+                self.entetes_api.perform(action);
+                Task::none()
+            }
+            Message::CorpsApi(action) => {
+                self.corps_api.perform(action);
+                Task::none()
+            }
+            Message::AppelerApi => {
+                self.changer_fournisseur();
+                let fournisseur = self.radio_fournisseur;
+                let auth = self.auth_clients.get(&fournisseur).cloned().unwrap_or_default();
+                let requete = RequeteApi {
+                    entetes: self.entetes_api.text(),
+                    corps: self.corps_api.text().trim_end().to_owned(),
+                    ..self.requete_api.clone()
+                };
+                let task = appeler_api(fournisseur, self.secret.clone(), self.demande(), auth, requete);
+                self.reponse_api = None;
+                self.erreur = String::new();
+                self.en_traitement = true;
+                Task::perform(task, |r| Message::ReponseApi(r.map_err(|e| format!("{e:#}"))))
+            }
+            Message::ReponseApi(result) => {
+                match result {
+                    Ok((reponse, secret)) => {
+                        self.reponse_api = Some(reponse);
+                        self.secret = Some(secret);
+                    }
+                    Err(e) => self.erreur = e,
                 }
+                self.en_traitement = false;
+                Task::none()
+            }
+            Message::GetInfos => {
+                self.changer_fournisseur();
                 let fournisseur = self.radio_fournisseur;
                 let secret = self.secret.clone();
-                let task = get_infos(fournisseur, secret);
+                let auth = self.auth_clients.get(&fournisseur).cloned().unwrap_or_default();
+                let task = get_infos(fournisseur, secret, self.demande(), auth);
                 self.infos = None;
+                self.inspection = None;
                 self.erreur = String::new();
                 self.en_traitement = true;
                 Task::perform(task, |i| Message::Infos(i.map_err(|e| format!("{e:#}"))))
@@ -183,6 +386,51 @@ impl App {
                 self.timeline.now(now);
                 Task::none()
             }
+            Message::Inspecter => {
+                self.inspection = match (&self.inspection, &self.secret) {
+                    (None, Some(pkce)) => match jwt::inspecter(pkce.secret()) {
+                        jwt::FormatJeton::Jwt { entete, charge } => {
+                            let exp = charge.get("exp").and_then(Value::as_u64);
+                            let liaison = pkce.dpop().map(|cle| dpop::liaison(cle, Some(&charge)));
+                            Some(Inspection::Jwt(jwt::lignes(&entete, &charge), exp, liaison))
+                        }
+                        jwt::FormatJeton::Opaque => Some(Inspection::Opaque(pkce.dpop().map(|cle| dpop::liaison(cle, None)))),
+                    },
+                    _ => None,
+                };
+                Task::none()
+            }
+            Message::Seconde => Task::none(),
+        }
+    }
+
+    // Le jeton obtenu d'un autre fournisseur n'est pas réutilisé
+    fn changer_fournisseur(&mut self) {
+        let fournisseur = self.radio_fournisseur.to_string();
+        if self.fournisseur != fournisseur {
+            self.secret = None;
+            self.fournisseur = fournisseur;
+        }
+    }
+
+    fn demande(&self) -> Demande {
+        let scopes = SCOPES
+            .iter()
+            .zip(self.scopes)
+            .filter(|&(_, coche)| coche)
+            .map(|(scope, _)| scope.to_string());
+        Demande::saisie(scopes, &self.autres_scopes, &self.parametres)
+    }
+
+    // exp fait foi pour un JWT, sinon la durée de vie retournée avec le jeton
+    fn expire_dans(&self) -> Option<std::time::Duration> {
+        let pkce = self.secret.as_ref()?;
+        match self.inspection {
+            Some(Inspection::Jwt(_, Some(exp), _)) => {
+                let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                Some(std::time::Duration::from_secs(exp.saturating_sub(maintenant)))
+            }
+            _ => Some(pkce.expire_dans()),
         }
     }
 
@@ -206,19 +454,120 @@ impl App {
                     .map(Element::from)
                     .collect::<Vec<_>>()
             )
-            .spacing(5)
+            .spacing(5),
+            text("Mode:"),
+            row([Mode::Code, Mode::ClientCredentials, Mode::Api]
+                .into_iter()
+                .map(|mode| radio(format!("{mode}"), mode, Some(self.mode), Message::ModeChanged).size(18))
+                .map(Element::from))
+            .spacing(10)
         ]
         .spacing(10);
 
-        let bouton = if !self.en_traitement {
-            button("Userinfos").on_press(Message::GetInfos)
-        } else {
-            button("Userinfos")
-        };
+        let options = match self.mode {
+            Mode::Code => column![
+                text("Scopes:"),
+                row(SCOPES
+                    .iter()
+                    .enumerate()
+                    .map(|(i, scope)| checkbox(*scope, self.scopes[i])
+                        .on_toggle(move |coche| Message::ScopeChanged(i, coche))
+                        .size(18))
+                    .map(Element::from))
+                .spacing(10),
+                text_input("Autres scopes", &self.autres_scopes).on_input(Message::AutresScopesChanged),
+                text("Paramètres:"),
+                text_input("prompt=consent&login_hint=...", &self.parametres).on_input(Message::ParametresChanged),
+            ],
+            Mode::ClientCredentials => column![
+                text("Scopes:"),
+                text_input("https://graph.microsoft.com/.default", &self.client_credentials.scopes).on_input(Message::CcScopesChanged),
+                text("Resource:"),
+                text_input("https://api.exemple.com", &self.client_credentials.resource).on_input(Message::CcResourceChanged),
+                text("Ressource protégée (GET):"),
+                text_input("https://graph.microsoft.com/v1.0/users", &self.client_credentials.url).on_input(Message::CcUrlChanged),
+            ],
+            Mode::Api => column![
+                text("Requête:"),
+                row![
+                    pick_list(Methode::ALL, Some(self.requete_api.methode), Message::MethodeApiChanged),
+                    text_input("https://graph.microsoft.com/v1.0/me", &self.requete_api.url).on_input(Message::UrlApiChanged),
+                ]
+                .spacing(5),
+                text("Entêtes (Nom: valeur):"),
+                text_editor(&self.entetes_api).on_action(Message::EntetesApi).height(60),
+                text("Corps:"),
+                text_editor(&self.corps_api).on_action(Message::CorpsApi).height(80),
+            ],
+        }
+        .spacing(5)
+        .width(350);
+
+        let auth = self.auth_clients.get(&self.radio_fournisseur).cloned().unwrap_or_default();
+        let mut auth_client = column![
+            text("Authentification au token endpoint:"),
+            pick_list(MethodeAuth::ALL, Some(auth.methode), Message::MethodeAuthChanged),
+        ]
+        .spacing(5)
+        .width(350);
+        if self.mode == Mode::Code {
+            auth_client = auth_client
+                .push(
+                    checkbox("Requête d'autorisation signée (JAR)", auth.jar)
+                        .on_toggle(Message::JarChanged)
+                        .size(18),
+                )
+                .push(pick_list(ModePar::ALL, Some(auth.par), Message::ParChanged))
+                .push(checkbox("Jeton lié par DPoP", auth.dpop).on_toggle(Message::DpopChanged).size(18));
+        }
+        if auth.methode == MethodeAuth::PrivateKeyJwt || (self.mode == Mode::Code && auth.jar) {
+            auth_client = auth_client.push(text_input("Clé privée PEM (RSA ou EC P-256)", &auth.cle).on_input(Message::CleChanged));
+        }
+
+        let boutons = match self.mode {
+            Mode::Code => {
+                let bouton = if !self.en_traitement {
+                    button("Userinfos").on_press(Message::GetInfos)
+                } else {
+                    button("Userinfos")
+                };
+
+                let bouton_jeton = if !self.en_traitement && self.secret.is_some() {
+                    button("Jeton").on_press(Message::Inspecter)
+                } else {
+                    button("Jeton")
+                };
+
+                row![bouton, bouton_jeton]
+            }
+            Mode::ClientCredentials => {
+                let bouton = if !self.en_traitement {
+                    button("Client credentials").on_press(Message::GetClientCredentials)
+                } else {
+                    button("Client credentials")
+                };
+
+                row![bouton]
+            }
+            Mode::Api => {
+                let bouton = if !self.en_traitement && !self.requete_api.url.trim().is_empty() {
+                    button("Envoyer").on_press(Message::AppelerApi)
+                } else {
+                    button("Envoyer")
+                };
+
+                row![bouton]
+            }
+        }
+        .spacing(10);
 
         let infos = match &self.infos {
             Some(data) => {
-                let fournisseur = text(format!("Userinfos {}", &self.fournisseur)).size(24);
+                let titre = match self.mode {
+                    Mode::ClientCredentials => "Client credentials",
+                    _ => "Userinfos",
+                };
+                let fournisseur = text(format!("{titre} {}", &self.fournisseur)).size(24);
 
                 if self.en_traitement {
                     column![fournisseur]
@@ -235,11 +584,73 @@ impl App {
             _ => column![""],
         };
 
+        let infos = match &self.ressource {
+            Some(ressource) if !self.en_traitement => infos.push(
+                column![
+                    text("Ressource protégée").size(24),
+                    Table::new(ressource)
+                        .font_size(16)
+                        .header_color(Color::from_rgb8(255, 165, 0))
+                        .cell_padding(Padding::new(5.).left(7).right(3))
+                ]
+                .spacing(5),
+            ),
+            _ => infos,
+        };
+
+        let infos = match &self.reponse_api {
+            Some(reponse) if !self.en_traitement => infos.push(
+                column![
+                    text(format!("API {}", &self.fournisseur)).size(24),
+                    text(format!("Statut {}", reponse.statut)),
+                    Table::new(&reponse.entetes)
+                        .font_size(16)
+                        .header_color(Color::from_rgb8(255, 165, 0))
+                        .cell_padding(Padding::new(5.).left(7).right(3)),
+                    Table::new(&reponse.corps)
+                        .font_size(16)
+                        .header_color(Color::from_rgb8(255, 165, 0))
+                        .cell_padding(Padding::new(5.).left(7).right(3))
+                ]
+                .spacing(5),
+            ),
+            _ => infos,
+        };
+
+        let infos = match &self.inspection {
+            Some(inspection) => {
+                let compteur = match self.expire_dans() {
+                    Some(restant) if !restant.is_zero() => format!("Expire dans {}:{:02}", restant.as_secs() / 60, restant.as_secs() % 60),
+                    _ => "Expiré".to_owned(),
+                };
+                let jeton = match inspection {
+                    Inspection::Jwt(lignes, _, liaison) => column![
+                        text("Jeton d'accès JWT").size(24),
+                        text(compteur),
+                        text(liaison.clone().unwrap_or_default()),
+                        Table::new(lignes)
+                            .font_size(16)
+                            .header_color(Color::from_rgb8(255, 165, 0))
+                            .cell_padding(Padding::new(5.).left(7).right(3))
+                    ],
+                    Inspection::Opaque(liaison) => column![
+                        text("Jeton d'accès opaque").size(24),
+                        text(compteur),
+                        text("Ce jeton ne peut pas être décodé par le client"),
+                        text(liaison.clone().unwrap_or_default())
+                    ],
+                };
+                infos.push(jeton.spacing(5))
+            }
+            None => infos,
+        }
+        .spacing(10);
+
         let erreur = text(&self.erreur).color([1.0, 0.0, 0.0]);
 
         container(
             row![
-                column![image, titre, fournisseur, bouton, erreur].spacing(10),
+                column![image, titre, fournisseur, options, auth_client, boutons, erreur].spacing(10),
                 anim!(self.container, &self.timeline, infos)
             ]
             .spacing(20),
@@ -255,25 +666,43 @@ impl App {
     }
 
     fn subscription(&self) -> Subscription<Message> {
+        let compteur = match self.inspection {
+            Some(_) => iced::time::every(std::time::Duration::from_secs(1)).map(|_| Message::Seconde),
+            None => Subscription::none(),
+        };
+
         Subscription::batch([
             stream_event_mode_couleur().map(Message::ModeCouleurChanged),
             self.timeline.as_subscription::<Event>().map(Message::Tick),
+            compteur,
         ])
     }
 }
 
-async fn get_infos(fournisseur: Fournisseur, secret: Option<Pkce>) -> Result<(Option<Vec<Vec<String>>>, Option<Pkce>)> {
-    let secret = match secret {
-        Some(pkce) if pkce.is_expired() => Some(Pkce::new(&fournisseur).await?),
-        Some(pkce) => Some(pkce),
-        None => Some(Pkce::new(&fournisseur).await?),
-    };
+// Le jeton est réutilisé tant qu'il n'est pas expiré et que la demande est la même
+async fn jeton(fournisseur: Fournisseur, secret: Option<Pkce>, demande: &Demande, auth: &AuthClient) -> Result<Pkce> {
+    match secret {
+        Some(pkce) if !pkce.is_expired() && pkce.demande() == demande => Ok(pkce),
+        _ => Pkce::new(&fournisseur, demande, auth).await,
+    }
+}
+
+async fn get_infos(
+    fournisseur: Fournisseur,
+    secret: Option<Pkce>,
+    demande: Demande,
+    auth: AuthClient,
+) -> Result<(Option<Vec<Vec<String>>>, Option<Pkce>)> {
+    let secret = Some(jeton(fournisseur, secret, &demande, &auth).await?);
 
-    let value = ureq::get(fournisseur.userinfos())
-        .set("Authorization", &format!("Bearer {}", secret.clone().unwrap().secret()))
-        .timeout(std::time::Duration::from_secs(20))
-        .call()?
-        .into_json::<Value>()?;
+    let pkce = secret.as_ref().expect("jeton obtenu");
+    let value = dpop::envoyer(
+        || ureq::get(fournisseur.userinfos()).timeout(std::time::Duration::from_secs(20)),
+        pkce.secret(),
+        pkce.dpop(),
+        None,
+    )?
+    .into_json::<Value>()?;
 
     match value {
         Value::Object(map) => {
@@ -286,6 +715,18 @@ async fn get_infos(fournisseur: Fournisseur, secret: Option<Pkce>) -> Result<(Op
     }
 }
 
+async fn appeler_api(
+    fournisseur: Fournisseur,
+    secret: Option<Pkce>,
+    demande: Demande,
+    auth: AuthClient,
+    requete: RequeteApi,
+) -> Result<(ReponseApi, Pkce)> {
+    let secret = jeton(fournisseur, secret, &demande, &auth).await?;
+    let reponse = api::appeler(&requete, secret.secret(), secret.dpop())?;
+    Ok((reponse, secret))
+}
+
 fn from(p: [u16; 4]) -> Padding {
     Padding {
         top: f32::from(p[0]),
@@ -0,0 +1,85 @@
+use crate::config::{self, MethodeAuth};
+use crate::session::{random_token, Fournisseur};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use oauth2::basic::{BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse, BasicTokenType};
+use oauth2::{AuthType, AuthUrl, Client, ClientId, ClientSecret, ExtraTokenFields, StandardRevocableToken, StandardTokenResponse, TokenUrl};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TYPE_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+// BasicTokenResponse ignore l'id_token: il est conservé pour l'identité de la session (sub, sid)
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ChampsOidc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+impl ExtraTokenFields for ChampsOidc {}
+
+pub type ClientOidc = Client<
+    BasicErrorResponse,
+    StandardTokenResponse<ChampsOidc, BasicTokenType>,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+// client_secret_post et client_secret_basic sont gérés par oauth2. Pour les méthodes JWT,
+// le secret n'est pas transmis et l'assertion est ajoutée à la requête au token endpoint.
+pub fn client_oauth(f: &Fournisseur) -> ClientOidc {
+    let (id, secret) = f.secrets();
+    let id = ClientId::new(id.to_owned());
+    let secret = ClientSecret::new(secret.to_owned());
+
+    let (url_auth, url_token) = f.endpoints();
+    let auth_url = AuthUrl::new(url_auth.to_owned()).unwrap();
+    let token_url = TokenUrl::new(url_token.to_owned()).unwrap();
+
+    let (secret, auth_type) = match config::fournisseur(&f.to_string()).methode_auth {
+        MethodeAuth::ClientSecretPost => (Some(secret), AuthType::RequestBody),
+        MethodeAuth::ClientSecretBasic => (Some(secret), AuthType::BasicAuth),
+        MethodeAuth::ClientSecretJwt | MethodeAuth::PrivateKeyJwt => (None, AuthType::RequestBody),
+    };
+
+    ClientOidc::new(id, secret, auth_url, Some(token_url)).set_auth_type(auth_type)
+}
+
+// Paramètres client_assertion_type et client_assertion à ajouter à la requête au token endpoint
+pub fn parametres_assertion(f: &Fournisseur) -> Result<Vec<(&'static str, String)>, jsonwebtoken::errors::Error> {
+    Ok(match assertion(f)? {
+        Some(jwt) => vec![("client_assertion_type", TYPE_ASSERTION.to_owned()), ("client_assertion", jwt)],
+        None => Vec::new(),
+    })
+}
+
+// Assertion client_secret_jwt (HS256 avec le secret) ou private_key_jwt (clé configurée)
+fn assertion(f: &Fournisseur) -> Result<Option<String>, jsonwebtoken::errors::Error> {
+    let config = config::fournisseur(&f.to_string());
+    let (id, secret) = f.secrets();
+    let (cle, mut entete) = match (config.methode_auth, config.cle) {
+        (MethodeAuth::ClientSecretJwt, _) => (EncodingKey::from_secret(secret.as_bytes()), Header::new(Algorithm::HS256)),
+        (MethodeAuth::PrivateKeyJwt, Some(cle)) => {
+            let mut entete = Header::new(cle.alg);
+            entete.kid = cle.kid;
+            (cle.cle, entete)
+        }
+        _ => return Ok(None),
+    };
+    entete.typ = Some("JWT".to_owned());
+
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (_, url_token) = f.endpoints();
+    let claims = json!({
+        "iss": id,
+        "sub": id,
+        "aud": url_token,
+        "jti": random_token(32),
+        "iat": maintenant,
+        "exp": maintenant + 300,
+    });
+
+    encode(&entete, &claims, &cle).map(Some)
+}
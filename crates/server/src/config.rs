@@ -0,0 +1,220 @@
+use crate::erreur::Erreur;
+use jsonwebtoken::{Algorithm, EncodingKey};
+use lazy_static::lazy_static;
+use oauth2::url::{Host, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+lazy_static! {
+    static ref FOURNISSEURS: RwLock<HashMap<String, ConfigFournisseur>> = RwLock::new(HashMap::new());
+    static ref ORIGINES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+    static ref ORIGINES_API: RwLock<Vec<String>> = RwLock::new(Vec::new());
+    static ref EXPIRATION_DEFAUT: RwLock<Duration> = RwLock::new(Duration::from_secs(60));
+}
+
+// Méthodes d'authentification du client au token endpoint (OIDC Core 9)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MethodeAuth {
+    #[default]
+    ClientSecretPost,
+    ClientSecretBasic,
+    ClientSecretJwt,
+    PrivateKeyJwt,
+}
+
+impl fmt::Display for MethodeAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let methode = match self {
+            MethodeAuth::ClientSecretPost => "client_secret_post",
+            MethodeAuth::ClientSecretBasic => "client_secret_basic",
+            MethodeAuth::ClientSecretJwt => "client_secret_jwt",
+            MethodeAuth::PrivateKeyJwt => "private_key_jwt",
+        };
+        f.write_str(methode)
+    }
+}
+
+// Pushed Authorization Requests (RFC 9126): auto si l'OP annonce pushed_authorization_request_endpoint
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModePar {
+    #[default]
+    Auto,
+    Toujours,
+    Jamais,
+}
+
+#[derive(Clone)]
+pub struct CleClient {
+    pub cle: EncodingKey,
+    pub alg: Algorithm,
+    pub kid: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct ConfigFournisseur {
+    pub methode_auth: MethodeAuth,
+    pub cle: Option<CleClient>,
+    pub par: ModePar,
+    // Requête d'autorisation signée avec la clé du client (RFC 9101)
+    pub jar: bool,
+    // Jetons liés à une clé éphémère par des preuves DPoP (RFC 9449)
+    pub dpop: bool,
+}
+
+#[derive(Deserialize)]
+struct Fichier {
+    #[serde(default)]
+    methode_auth: MethodeAuth,
+    cle_privee: Option<PathBuf>,
+    alg: Option<String>,
+    kid: Option<String>,
+    #[serde(default)]
+    par: ModePar,
+    #[serde(default)]
+    jar: bool,
+    #[serde(default)]
+    dpop: bool,
+}
+
+// Le fichier de configuration est un objet JSON indexé par le nom du fournisseur:
+// { "Microsoft": { "methode_auth": "private_key_jwt", "cle_privee": "cle.pem", "alg": "RS256", "kid": "...", "par": "toujours", "jar": true, "dpop": true } }
+pub fn charger(chemin: &Path) -> Result<(), Box<dyn Error>> {
+    let contenu = std::fs::read(chemin).map_err(|e| format!("{}: {e}", chemin.to_string_lossy()))?;
+    let fichier: HashMap<String, Fichier> = serde_json::from_slice(&contenu)?;
+
+    let mut fournisseurs = HashMap::new();
+    for (nom, f) in fichier {
+        let cle = match (f.methode_auth, f.cle_privee) {
+            (MethodeAuth::PrivateKeyJwt, None) => return Err(format!("{nom}: private_key_jwt requiert cle_privee").into()),
+            (_, None) if f.jar => return Err(format!("{nom}: jar requiert cle_privee").into()),
+            (MethodeAuth::PrivateKeyJwt, Some(cle)) => Some(charger_cle(&cle, f.alg.as_deref(), f.kid)?),
+            (_, Some(cle)) if f.jar => Some(charger_cle(&cle, f.alg.as_deref(), f.kid)?),
+            _ => None,
+        };
+        fournisseurs.insert(
+            nom,
+            ConfigFournisseur {
+                methode_auth: f.methode_auth,
+                cle,
+                par: f.par,
+                jar: f.jar,
+                dpop: f.dpop,
+            },
+        );
+    }
+
+    *FOURNISSEURS.write().expect("Failed due to poisoned lock") = fournisseurs;
+    Ok(())
+}
+
+// Sans alg explicite, RS256 pour une clé RSA et ES256 pour une clé EC
+fn charger_cle(chemin: &Path, alg: Option<&str>, kid: Option<String>) -> Result<CleClient, Box<dyn Error>> {
+    let pem = std::fs::read(chemin).map_err(|e| format!("{}: {e}", chemin.to_string_lossy()))?;
+    let alg = match alg {
+        Some(alg) => Algorithm::from_str(alg)?,
+        None if EncodingKey::from_rsa_pem(&pem).is_ok() => Algorithm::RS256,
+        None => Algorithm::ES256,
+    };
+
+    let cle = match alg {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 => {
+            EncodingKey::from_rsa_pem(&pem)?
+        }
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(&pem)?,
+        _ => return Err(format!("{alg:?} n'est pas supporté pour private_key_jwt").into()),
+    };
+
+    Ok(CleClient { cle, alg, kid })
+}
+
+// Origines autorisées pour l'URI de redirection; sans configuration, seul le loopback est autorisé
+pub fn definir_origines(origines: &[String]) -> Result<(), Box<dyn Error>> {
+    let origines = origines
+        .iter()
+        .map(|origine| analyser_origine(origine).map(|url| url.origin().ascii_serialization()))
+        .collect::<Result<Vec<_>, _>>()?;
+    *ORIGINES.write().expect("Failed due to poisoned lock") = origines;
+    Ok(())
+}
+
+pub fn origine_autorisee(origine: &str) -> Result<Url, Erreur> {
+    let url = analyser_origine(origine)?;
+    let origines = ORIGINES.read().expect("Failed due to poisoned lock");
+    let autorisee = if origines.is_empty() {
+        match url.host() {
+            Some(Host::Domain(domaine)) => domaine == "localhost",
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        }
+    } else {
+        origines.contains(&url.origin().ascii_serialization())
+    };
+
+    if autorisee {
+        Ok(url)
+    } else {
+        Err(Erreur::OrigineNonAutorisee(origine.to_owned()))
+    }
+}
+
+// Origines permises pour les appels avec le jeton de /api et /client_credentials; sans configuration, toute adresse publique
+pub fn definir_origines_api(origines: &[String]) -> Result<(), Box<dyn Error>> {
+    let origines = origines
+        .iter()
+        .map(|origine| analyser_origine(origine).map(|url| url.origin().ascii_serialization()))
+        .collect::<Result<Vec<_>, _>>()?;
+    *ORIGINES_API.write().expect("Failed due to poisoned lock") = origines;
+    Ok(())
+}
+
+pub fn origines_api() -> Vec<String> {
+    ORIGINES_API.read().expect("Failed due to poisoned lock").clone()
+}
+
+// Une origine est un schéma http(s), un hôte et un port optionnel, sans chemin
+fn analyser_origine(origine: &str) -> Result<Url, Erreur> {
+    let invalide = || Erreur::OrigineInvalide(origine.to_owned());
+    let url = Url::parse(origine.trim_end_matches('/')).map_err(|_| invalide())?;
+    let valide = matches!(url.scheme(), "http" | "https")
+        && url.host().is_some()
+        && url.username().is_empty()
+        && url.password().is_none()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none();
+    if valide {
+        Ok(url)
+    } else {
+        Err(invalide())
+    }
+}
+
+pub fn definir_expiration_defaut(expiration: Duration) {
+    *EXPIRATION_DEFAUT.write().expect("Failed due to poisoned lock") = expiration;
+}
+
+pub fn expiration_defaut() -> Duration {
+    *EXPIRATION_DEFAUT.read().expect("Failed due to poisoned lock")
+}
+
+pub fn noms() -> Vec<String> {
+    FOURNISSEURS.read().expect("Failed due to poisoned lock").keys().cloned().collect()
+}
+
+pub fn fournisseur(nom: &str) -> ConfigFournisseur {
+    FOURNISSEURS
+        .read()
+        .expect("Failed due to poisoned lock")
+        .get(nom)
+        .cloned()
+        .unwrap_or_default()
+}
@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// Vrai lorsque le serveur est servi en HTTPS: les cookies sont alors Secure et préfixés __Host-
+static SECURISE: AtomicBool = AtomicBool::new(false);
+
+// Délai accordé pour s'authentifier auprès de l'OP
+pub const DUREE_AUTORISATION: Duration = Duration::from_secs(600);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    // Cookie envoyé avec un POST cross-site (response_mode=form_post); les navigateurs exigent Secure, donc HTTPS
+    None,
+    Lax,
+    Strict,
+}
+
+pub fn configurer(securise: bool) {
+    SECURISE.store(securise, Ordering::Relaxed);
+}
+
+pub fn securise() -> bool {
+    SECURISE.load(Ordering::Relaxed)
+}
+
+pub fn nom_session() -> &'static str {
+    noms(SECURISE.load(Ordering::Relaxed)).0
+}
+
+pub fn nom_csrf() -> &'static str {
+    noms(SECURISE.load(Ordering::Relaxed)).1
+}
+
+// Le cookie de session n'est pas accessible au javascript
+pub fn session(valeur: &str, same_site: SameSite, max_age: Duration) -> String {
+    let securise = SECURISE.load(Ordering::Relaxed);
+    set_cookie(noms(securise).0, valeur, same_site, true, max_age, securise)
+}
+
+// Le javascript recopie le cookie Csrf dans l'entête X-Csrf-Token
+pub fn csrf(valeur: &str, max_age: Duration) -> String {
+    let securise = SECURISE.load(Ordering::Relaxed);
+    set_cookie(noms(securise).1, valeur, SameSite::Strict, false, max_age, securise)
+}
+
+// Valeur d'un cookie de l'entête Cookie
+pub fn valeur(entete: &str, nom: &str) -> Option<String> {
+    entete
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|&(n, _)| n == nom)
+        .map(|(_, v)| v.to_owned())
+}
+
+fn noms(securise: bool) -> (&'static str, &'static str) {
+    if securise {
+        ("__Host-Session-Id", "__Host-Csrf-Token")
+    } else {
+        ("Session-Id", "Csrf-Token")
+    }
+}
+
+fn set_cookie(nom: &str, valeur: &str, same_site: SameSite, http_only: bool, max_age: Duration, securise: bool) -> String {
+    let mut cookie = format!("{nom}={valeur}; Path=/; Max-Age={}; SameSite={same_site:?}", max_age.as_secs());
+    if http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    if securise {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_https() {
+        assert_eq!(
+            set_cookie(noms(true).0, "LOL", SameSite::Lax, true, DUREE_AUTORISATION, true),
+            "__Host-Session-Id=LOL; Path=/; Max-Age=600; SameSite=Lax; HttpOnly; Secure"
+        );
+    }
+
+    #[test]
+    fn csrf_http() {
+        assert_eq!(
+            set_cookie(noms(false).1, "BOUH", SameSite::Strict, false, Duration::from_secs(3599), false),
+            "Csrf-Token=BOUH; Path=/; Max-Age=3599; SameSite=Strict"
+        );
+    }
+
+    #[test]
+    fn form_post_https() {
+        assert_eq!(
+            set_cookie(noms(true).0, "LOL", SameSite::None, true, DUREE_AUTORISATION, true),
+            "__Host-Session-Id=LOL; Path=/; Max-Age=600; SameSite=None; HttpOnly; Secure"
+        );
+    }
+
+    #[test]
+    fn valeur_cookie() {
+        let entete = "Csrf-Token=LOL; __Host-Session-Id=BOUH";
+        assert_eq!(valeur(entete, "__Host-Session-Id"), Some("BOUH".to_owned()));
+        assert_eq!(valeur(entete, "Session-Id"), None);
+    }
+}
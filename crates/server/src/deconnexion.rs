@@ -0,0 +1,175 @@
+use crate::decouverte;
+use crate::session::{Fournisseur, Identite};
+use commun::jwt::{self, FormatJeton};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+// OpenID Connect Back-Channel Logout 1.0, 2.4
+const EVENEMENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+lazy_static! {
+    // jti reçus et leur exp: un logout token ne peut être rejoué
+    static ref JTI: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+// Sessions visées par un logout token valide; l'identité porte l'issuer du fournisseur
+pub struct Deconnexion {
+    pub fournisseur: Fournisseur,
+    pub identite: Identite,
+}
+
+// iss, sub et sid de l'id_token reçu du token endpoint. Le canal TLS avec l'OP tient lieu de validation de la signature.
+pub fn identite(id_token: Option<&str>) -> Identite {
+    match id_token.map(jwt::inspecter) {
+        Some(FormatJeton::Jwt { charge, .. }) => Identite {
+            iss: texte(&charge, "iss"),
+            sub: texte(&charge, "sub"),
+            sid: texte(&charge, "sid"),
+        },
+        _ => Identite::default(),
+    }
+}
+
+// Le fournisseur est celui dont l'issuer est annoncé par le jeton; la signature est ensuite vérifiée avec son JWKS
+pub async fn valider(logout_token: &str) -> Result<Deconnexion, String> {
+    let entete = decode_header(logout_token).map_err(|e| e.to_string())?;
+    let iss = match jwt::inspecter(logout_token) {
+        FormatJeton::Jwt { charge, .. } => texte(&charge, "iss").ok_or("iss absent")?,
+        FormatJeton::Opaque => return Err("le logout token doit être un JWT signé".to_owned()),
+    };
+
+    // Un fournisseur injoignable n'empêche pas de reconnaître l'issuer des autres
+    for f in Fournisseur::TOUS {
+        match decouverte::jwks(&f, false).await {
+            Ok((issuer, _)) if issuer == iss => {}
+            Ok(_) => continue,
+            Err(e) => {
+                warn!(fournisseur = %f, "discovery: {e}");
+                continue;
+            }
+        }
+
+        let (issuer, jwk) = decouverte::cle(&f, entete.kid.as_deref().unwrap_or_default()).await?;
+        let alg = jwt::algorithme(&jwk, entete.alg)?;
+        let cle = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+        let (identite, jti, exp) = verifier(logout_token, &cle, alg, &issuer, f.secrets().0.trim())?;
+        premiere_reception(jti, exp)?;
+        return Ok(Deconnexion { fournisseur: f, identite });
+    }
+
+    Err(format!("issuer inconnu: {iss}"))
+}
+
+// Règles de validation 2.6
+fn verifier(jeton: &str, cle: &DecodingKey, alg: Algorithm, issuer: &str, client_id: &str) -> Result<(Identite, String, u64), String> {
+    let mut validation = Validation::new(alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "iat", "exp"]);
+    let claims = decode::<Map<String, Value>>(jeton, cle, &validation).map_err(|e| e.to_string())?.claims;
+
+    if !claims
+        .get("events")
+        .and_then(|events| events.get(EVENEMENT))
+        .is_some_and(Value::is_object)
+    {
+        return Err(format!("l'événement {EVENEMENT} est absent"));
+    }
+    if claims.contains_key("nonce") {
+        return Err("un logout token ne doit pas contenir de nonce".to_owned());
+    }
+    let identite = Identite {
+        iss: Some(issuer.to_owned()),
+        sub: texte(&claims, "sub"),
+        sid: texte(&claims, "sid"),
+    };
+    if identite.sub.is_none() && identite.sid.is_none() {
+        return Err("sub ou sid est requis".to_owned());
+    }
+    let jti = texte(&claims, "jti").ok_or("jti absent")?;
+    let exp = claims.get("exp").and_then(Value::as_u64).unwrap_or_default();
+
+    Ok((identite, jti, exp))
+}
+
+fn premiere_reception(jti: String, exp: u64) -> Result<(), String> {
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut recus = JTI.lock().expect("Failed due to poisoned lock");
+    recus.retain(|_, &mut exp| exp + 60 >= maintenant);
+    match recus.insert(jti, exp) {
+        Some(_) => Err("logout token rejoué".to_owned()),
+        None => Ok(()),
+    }
+}
+
+fn texte(claims: &Map<String, Value>, nom: &str) -> Option<String> {
+    claims.get(nom).and_then(Value::as_str).map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    fn logout_token(claims: Value) -> String {
+        encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(b"LOL")).unwrap()
+    }
+
+    fn claims() -> Value {
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        json!({
+            "iss": "https://op.exemple.com",
+            "aud": "client",
+            "iat": maintenant,
+            "exp": maintenant + 120,
+            "jti": "bWJq",
+            "sid": "08a5019c-17e1-4977-8f42-65a12843ea02",
+            "events": { EVENEMENT: {} },
+        })
+    }
+
+    #[test]
+    fn logout_token_valide() {
+        let cle = DecodingKey::from_secret(b"LOL");
+        let (identite, jti, _) = verifier(&logout_token(claims()), &cle, Algorithm::HS256, "https://op.exemple.com", "client").unwrap();
+        assert_eq!(identite.sid.as_deref(), Some("08a5019c-17e1-4977-8f42-65a12843ea02"));
+        assert_eq!(jti, "bWJq");
+    }
+
+    #[test]
+    fn logout_token_invalide() {
+        let cle = DecodingKey::from_secret(b"LOL");
+        let valider = |claims: Value| verifier(&logout_token(claims), &cle, Algorithm::HS256, "https://op.exemple.com", "client");
+
+        let mut nonce = claims();
+        nonce["nonce"] = json!("n-0S6_WzA2Mj");
+        assert!(valider(nonce).is_err());
+
+        let mut sans_evenement = claims();
+        sans_evenement["events"] = json!({});
+        assert!(valider(sans_evenement).is_err());
+
+        let mut autre_client = claims();
+        autre_client["aud"] = json!("autre");
+        assert!(valider(autre_client).is_err());
+
+        let mut anonyme = claims();
+        anonyme.as_object_mut().unwrap().remove("sid");
+        assert!(valider(anonyme).is_err());
+
+        let cle = DecodingKey::from_secret(b"BOUH");
+        assert!(verifier(&logout_token(claims()), &cle, Algorithm::HS256, "https://op.exemple.com", "client").is_err());
+    }
+
+    #[test]
+    fn rejeu() {
+        assert!(premiere_reception("rejeu".to_owned(), u64::MAX / 2).is_ok());
+        assert!(premiere_reception("rejeu".to_owned(), u64::MAX / 2).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::sante;
+use crate::session::Fournisseur;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+const DUREE_CACHE: Duration = Duration::from_secs(3600);
+const DELAI: Duration = Duration::from_secs(5);
+// Un kid inconnu force la relecture du JWKS au plus une fois par intervalle
+const INTERVALLE_RELECTURE: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // Document discovery de chaque fournisseur; un échec n'est pas conservé
+    static ref DOCUMENTS: RwLock<HashMap<String, (Instant, Value)>> = RwLock::new(HashMap::new());
+    // issuer et JWKS de chaque fournisseur; relus à l'expiration du cache ou pour un kid inconnu
+    static ref JWKS: RwLock<HashMap<String, (Instant, String, JwkSet)>> = RwLock::new(HashMap::new());
+}
+
+pub async fn document(f: &Fournisseur) -> Result<Value, String> {
+    let nom = f.to_string();
+    if let Some((instant, document)) = DOCUMENTS.read().expect("Failed due to poisoned lock").get(&nom) {
+        if instant.elapsed() < DUREE_CACHE {
+            return Ok(document.clone());
+        }
+    }
+
+    let client = reqwest::Client::builder().timeout(DELAI).build().unwrap_or_default();
+    let document = sante::obtenir(&client, f.discovery()).await?;
+    DOCUMENTS
+        .write()
+        .expect("Failed due to poisoned lock")
+        .insert(nom, (Instant::now(), document.clone()));
+    Ok(document)
+}
+
+// Clé de signature de l'OP désignée par le kid d'un jeton, avec l'issuer attendu
+pub async fn cle(f: &Fournisseur, kid: &str) -> Result<(String, Jwk), String> {
+    let (issuer, cles) = jwks(f, false).await?;
+    if let Some(jwk) = cles.find(kid) {
+        return Ok((issuer, jwk.clone()));
+    }
+    let (issuer, cles) = jwks(f, true).await?;
+    let jwk = cles.find(kid).cloned().ok_or(format!("clé {kid} absente du JWKS"))?;
+    Ok((issuer, jwk))
+}
+
+pub async fn jwks(f: &Fournisseur, recharger: bool) -> Result<(String, JwkSet), String> {
+    let nom = f.to_string();
+    if let Some((instant, issuer, jwks)) = JWKS.read().expect("Failed due to poisoned lock").get(&nom) {
+        let delai = if recharger { INTERVALLE_RELECTURE } else { DUREE_CACHE };
+        if instant.elapsed() < delai {
+            return Ok((issuer.clone(), jwks.clone()));
+        }
+    }
+
+    let document = document(f).await?;
+    let issuer = champ(&document, "issuer").ok_or("issuer absent du document discovery")?;
+    let jwks_uri = champ(&document, "jwks_uri").ok_or("jwks_uri absent du document discovery")?;
+    let client = reqwest::Client::builder().timeout(DELAI).build().unwrap_or_default();
+    let jwks = serde_json::from_value::<JwkSet>(sante::obtenir(&client, &jwks_uri).await?).map_err(|e| format!("{jwks_uri}: {e}"))?;
+
+    JWKS.write()
+        .expect("Failed due to poisoned lock")
+        .insert(nom, (Instant::now(), issuer.clone(), jwks.clone()));
+    Ok((issuer, jwks))
+}
+
+pub fn champ(document: &Value, nom: &str) -> Option<String> {
+    document.get(nom).and_then(Value::as_str).map(str::to_owned)
+}
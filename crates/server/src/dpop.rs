@@ -0,0 +1,63 @@
+use commun::dpop;
+use oauth2::reqwest::{async_http_client, Error};
+use oauth2::{HttpRequest, HttpResponse};
+use warp::http::Method;
+
+// OAuth 2.0 Demonstrating Proof of Possession (RFC 9449): preuves et nonces dans commun::dpop
+pub use commun::dpop::CleDpop;
+
+// Client HTTP du token endpoint: la requête porte une preuve DPoP si la session a une clé
+pub async fn requete_jeton(cle: Option<&CleDpop>, requete: HttpRequest) -> Result<HttpResponse, Error<reqwest::Error>> {
+    let cle = match cle {
+        Some(cle) => cle,
+        None => return async_http_client(requete).await,
+    };
+
+    let url = requete.url.to_string();
+    let mut rejouee = false;
+    loop {
+        let mut requete = requete.clone();
+        let preuve = cle.preuve(Method::POST.as_str(), &url, None);
+        requete
+            .headers
+            .insert("DPoP", preuve.parse().map_err(|_| Error::Other("preuve DPoP".to_owned()))?);
+        let reponse = async_http_client(requete).await?;
+
+        let nonce = reponse.headers.get("DPoP-Nonce").and_then(|v| v.to_str().ok());
+        if rejouee || !dpop::rejouer_as(&url, reponse.status_code.as_u16(), nonce, &reponse.body) {
+            return Ok(reponse);
+        }
+        rejouee = true;
+    }
+}
+
+// Requête avec le jeton d'accès: DPoP avec une preuve liée au jeton si la session a une clé, sinon Bearer
+pub async fn envoyer(
+    construire: impl Fn() -> reqwest::RequestBuilder,
+    methode: &Method,
+    url: &str,
+    jeton: &str,
+    cle: Option<&CleDpop>,
+) -> Result<reqwest::Response, String> {
+    let cle = match cle {
+        Some(cle) => cle,
+        None => return construire().bearer_auth(jeton).send().await.map_err(|e| e.to_string()),
+    };
+
+    let mut rejouee = false;
+    loop {
+        let reponse = construire()
+            .header("Authorization", format!("DPoP {jeton}"))
+            .header("DPoP", cle.preuve(methode.as_str(), url, Some(jeton)))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let nonce = reponse.headers().get("DPoP-Nonce").and_then(|v| v.to_str().ok());
+        let www_authenticate = reponse.headers().get("WWW-Authenticate").and_then(|v| v.to_str().ok());
+        if rejouee || !dpop::rejouer_rs(url, reponse.status().as_u16(), nonce, www_authenticate) {
+            return Ok(reponse);
+        }
+        rejouee = true;
+    }
+}
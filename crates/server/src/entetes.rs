@@ -0,0 +1,63 @@
+use lazy_static::lazy_static;
+use std::error::Error;
+use std::sync::RwLock;
+use warp::http::header::{HeaderName, HeaderValue};
+use warp::reply::Response;
+use warp::Reply;
+
+// Les scripts et styles de static/ sont des fichiers: ni 'unsafe-inline' ni 'unsafe-eval'
+pub const CSP_DEFAUT: &str = "default-src 'none'; script-src 'self'; style-src 'self'; img-src 'self'; \
+    connect-src 'self'; form-action 'self'; frame-ancestors 'none'; base-uri 'none'";
+pub const FRAME_OPTIONS_DEFAUT: &str = "DENY";
+pub const REFERRER_POLICY_DEFAUT: &str = "no-referrer";
+pub const PERMISSIONS_POLICY_DEFAUT: &str = "camera=(), microphone=(), geolocation=(), payment=(), usb=()";
+
+lazy_static! {
+    static ref ENTETES: RwLock<Vec<(HeaderName, HeaderValue)>> = RwLock::new(
+        entetes(CSP_DEFAUT, 0, FRAME_OPTIONS_DEFAUT, REFERRER_POLICY_DEFAUT, PERMISSIONS_POLICY_DEFAUT).expect("entêtes par défaut valides")
+    );
+}
+
+// Une valeur vide retire l'entête; HSTS n'est émis qu'avec une durée non nulle
+pub fn configurer(csp: &str, hsts_max_age: u64, frame_options: &str, referrer_policy: &str, permissions_policy: &str) -> Result<(), Box<dyn Error>> {
+    *ENTETES.write().expect("Failed due to poisoned lock") = entetes(csp, hsts_max_age, frame_options, referrer_policy, permissions_policy)?;
+    Ok(())
+}
+
+fn entetes(
+    csp: &str,
+    hsts_max_age: u64,
+    frame_options: &str,
+    referrer_policy: &str,
+    permissions_policy: &str,
+) -> Result<Vec<(HeaderName, HeaderValue)>, Box<dyn Error>> {
+    let hsts = if hsts_max_age > 0 {
+        format!("max-age={hsts_max_age}; includeSubDomains")
+    } else {
+        String::default()
+    };
+    [
+        ("content-security-policy", csp),
+        ("strict-transport-security", hsts.as_str()),
+        ("x-frame-options", frame_options),
+        ("referrer-policy", referrer_policy),
+        ("permissions-policy", permissions_policy),
+        ("x-content-type-options", "nosniff"),
+    ]
+    .into_iter()
+    .filter(|(_, valeur)| !valeur.is_empty())
+    .map(|(nom, valeur)| {
+        let valeur = HeaderValue::from_str(valeur).map_err(|_| format!("valeur invalide pour l'entête {nom}: {valeur}"))?;
+        Ok((HeaderName::from_static(nom), valeur))
+    })
+    .collect()
+}
+
+// Les entêtes déjà posés par la réponse sont conservés
+pub fn appliquer(reply: impl Reply) -> Response {
+    let mut response = reply.into_response();
+    for (nom, valeur) in ENTETES.read().expect("Failed due to poisoned lock").iter() {
+        response.headers_mut().entry(nom).or_insert_with(|| valeur.clone());
+    }
+    response
+}
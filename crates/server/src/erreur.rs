@@ -0,0 +1,208 @@
+use oauth2::basic::BasicErrorResponse;
+use oauth2::RequestTokenError;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::Duration;
+use tracing::warn;
+use warp::http::response::Builder;
+use warp::http::{Error, Response, StatusCode};
+
+const TYPE_PROBLEME: &str = "urn:test-oidc:erreur:";
+
+// Erreurs retournées au client, avec un code stable pour chaque cas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Erreur {
+    CsrfInvalide,
+    CookieSessionInexistant,
+    SessionInexistante,
+    SessionDejaAuthentifiee,
+    NonAuthentifie,
+    CodeManquant,
+    StateManquant,
+    StateInvalide,
+    RequeteInvalide(String),
+    OrigineInvalide(String),
+    OrigineNonAutorisee(String),
+    AssertionClient(String),
+    // Appel avec le jeton vers une adresse non publique ou une origine non configurée
+    CibleNonAutorisee(String),
+    // error et error_description retournés par l'OP (RFC 6749 4.1.2.1 et 5.2)
+    Fournisseur { error: String, description: Option<String> },
+    FournisseurInjoignable(String),
+    LogoutTokenInvalide(String),
+    // Le cookie de session scellé dépasserait la taille acceptée par les navigateurs
+    SessionTropVolumineuse(usize),
+    // Réponse JARM invalide ou absente alors qu'elle a été demandée
+    ReponseAutorisationInvalide(String),
+    // Délai avant la prochaine requête acceptée
+    TropDeRequetes(Duration),
+}
+
+impl Erreur {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Erreur::CsrfInvalide => "csrf_invalide",
+            Erreur::CookieSessionInexistant => "cookie_session_inexistant",
+            Erreur::SessionInexistante => "session_inexistante",
+            Erreur::SessionDejaAuthentifiee => "session_deja_authentifiee",
+            Erreur::NonAuthentifie => "non_authentifie",
+            Erreur::CodeManquant => "code_manquant",
+            Erreur::StateManquant => "state_manquant",
+            Erreur::StateInvalide => "state_invalide",
+            Erreur::RequeteInvalide(_) => "requete_invalide",
+            Erreur::OrigineInvalide(_) => "origine_invalide",
+            Erreur::OrigineNonAutorisee(_) => "origine_non_autorisee",
+            Erreur::AssertionClient(_) => "assertion_client",
+            Erreur::CibleNonAutorisee(_) => "cible_non_autorisee",
+            Erreur::Fournisseur { .. } => "erreur_fournisseur",
+            Erreur::FournisseurInjoignable(_) => "fournisseur_injoignable",
+            Erreur::LogoutTokenInvalide(_) => "logout_token_invalide",
+            Erreur::ReponseAutorisationInvalide(_) => "reponse_autorisation_invalide",
+            Erreur::SessionTropVolumineuse(_) => "session_trop_volumineuse",
+            Erreur::TropDeRequetes(_) => "trop_de_requetes",
+        }
+    }
+
+    pub fn statut(&self) -> StatusCode {
+        match self {
+            Erreur::CsrfInvalide => StatusCode::FORBIDDEN,
+            Erreur::NonAuthentifie => StatusCode::UNAUTHORIZED,
+            Erreur::AssertionClient(_) | Erreur::SessionTropVolumineuse(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Erreur::FournisseurInjoignable(_) => StatusCode::BAD_GATEWAY,
+            Erreur::TropDeRequetes(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn titre(&self) -> &'static str {
+        match self {
+            Erreur::CsrfInvalide => "Jeton Csrf invalide",
+            Erreur::CookieSessionInexistant => "Cookie de session inexistant",
+            Erreur::SessionInexistante => "Session inexistante",
+            Erreur::SessionDejaAuthentifiee => "Session déjà authentifiée",
+            Erreur::NonAuthentifie => "Session inexistante ou non authentifiée",
+            Erreur::CodeManquant => "Code d'autorisation manquant",
+            Erreur::StateManquant => "Paramètre state manquant",
+            Erreur::StateInvalide => "Paramètre state invalide",
+            Erreur::RequeteInvalide(_) => "Requête invalide",
+            Erreur::OrigineInvalide(_) => "Origine invalide",
+            Erreur::OrigineNonAutorisee(_) => "Origine non autorisée",
+            Erreur::AssertionClient(_) => "Assertion client impossible",
+            Erreur::CibleNonAutorisee(_) => "Cible de l'appel non autorisée",
+            Erreur::Fournisseur { .. } => "Erreur retournée par le fournisseur",
+            Erreur::FournisseurInjoignable(_) => "Fournisseur injoignable",
+            Erreur::LogoutTokenInvalide(_) => "Logout token invalide",
+            Erreur::ReponseAutorisationInvalide(_) => "Réponse d'autorisation invalide",
+            Erreur::SessionTropVolumineuse(_) => "Session trop volumineuse pour le cookie",
+            Erreur::TropDeRequetes(_) => "Trop de requêtes",
+        }
+    }
+
+    // Réponse application/problem+json (RFC 7807)
+    pub fn probleme(&self) -> Result<Response<String>, Error> {
+        warn!(code = self.code(), "{self}");
+        let mut probleme = json!({
+            "type": format!("{TYPE_PROBLEME}{}", self.code()),
+            "title": self.titre(),
+            "status": self.statut().as_u16(),
+            "detail": self.to_string(),
+            "code": self.code(),
+        });
+        if let Erreur::Fournisseur { error, description } = self {
+            probleme["error"] = Value::String(error.to_owned());
+            if let Some(description) = description {
+                probleme["error_description"] = Value::String(description.to_owned());
+            }
+        }
+
+        self.reponse()
+            .header("Content-Type", "application/problem+json")
+            .body(probleme.to_string())
+    }
+
+    // Page d'erreur pour les routes atteintes par le navigateur (retour de l'OP)
+    pub fn page(&self) -> Result<Response<String>, Error> {
+        warn!(code = self.code(), "{self}");
+        let page = format!(
+            r#"<!DOCTYPE html>
+<html lang="fr">
+<head><meta charset="utf-8"><title>{titre}</title></head>
+<body>
+<h2>{titre}</h2>
+<p>{detail}</p>
+<p><small>{code}</small></p>
+<p><a href="/static/userinfos.htm">Retour</a></p>
+</body>
+</html>
+"#,
+            titre = echapper(self.titre()),
+            detail = echapper(&self.to_string()),
+            code = self.code(),
+        );
+
+        self.reponse().header("Content-Type", "text/html; charset=utf-8").body(page)
+    }
+
+    fn reponse(&self) -> Builder {
+        let reponse = Response::builder().status(self.statut());
+        match self {
+            Erreur::TropDeRequetes(delai) => reponse.header("Retry-After", secondes(delai)),
+            _ => reponse,
+        }
+    }
+}
+
+impl fmt::Display for Erreur {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Erreur::RequeteInvalide(detail)
+            | Erreur::OrigineInvalide(detail)
+            | Erreur::OrigineNonAutorisee(detail)
+            | Erreur::AssertionClient(detail)
+            | Erreur::CibleNonAutorisee(detail)
+            | Erreur::FournisseurInjoignable(detail)
+            | Erreur::LogoutTokenInvalide(detail)
+            | Erreur::ReponseAutorisationInvalide(detail) => {
+                write!(f, "{}: {detail}", self.titre())
+            }
+            Erreur::Fournisseur {
+                error,
+                description: Some(description),
+            } => write!(f, "{error}: {description}"),
+            Erreur::Fournisseur { error, description: None } => f.write_str(error),
+            Erreur::TropDeRequetes(delai) => write!(f, "{}, réessayer dans {} s", self.titre(), secondes(delai)),
+            Erreur::SessionTropVolumineuse(taille) => write!(f, "{}: {taille} octets", self.titre()),
+            _ => f.write_str(self.titre()),
+        }
+    }
+}
+
+impl std::error::Error for Erreur {}
+
+// Les erreurs du token endpoint sont propagées telles quelles
+impl<RE: std::error::Error + 'static> From<RequestTokenError<RE, BasicErrorResponse>> for Erreur {
+    fn from(e: RequestTokenError<RE, BasicErrorResponse>) -> Self {
+        match e {
+            RequestTokenError::ServerResponse(reponse) => Erreur::Fournisseur {
+                error: reponse.error().to_string(),
+                description: reponse.error_description().cloned(),
+            },
+            RequestTokenError::Request(e) => Erreur::FournisseurInjoignable(e.to_string()),
+            RequestTokenError::Parse(e, _) => Erreur::FournisseurInjoignable(e.to_string()),
+            RequestTokenError::Other(e) => Erreur::FournisseurInjoignable(e),
+        }
+    }
+}
+
+// Retry-After est un nombre entier de secondes, arrondi au supérieur
+fn secondes(delai: &Duration) -> u64 {
+    delai.as_secs() + u64::from(delai.subsec_nanos() > 0)
+}
+
+fn echapper(texte: &str) -> String {
+    texte
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,83 @@
+use crate::config::{self, CleClient};
+use crate::decouverte;
+use crate::erreur::Erreur;
+use crate::session::{random_token, Fournisseur};
+use jsonwebtoken::{encode, Header};
+use oauth2::url::Url;
+use serde_json::{Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// JWT-Secured Authorization Request (RFC 9101)
+const TYPE_OBJET: &str = "oauth-authz-req+jwt";
+
+// Ajoute le paramètre request à la requête d'autorisation. Les paramètres en clair sont conservés:
+// OIDC Core 6.1 exige response_type, client_id et scope dans la query string.
+pub async fn signer(f: &Fournisseur, mut authorize_url: Url) -> Result<Url, Erreur> {
+    let config = config::fournisseur(&f.to_string());
+    let cle = match (config.jar, config.cle) {
+        (true, Some(cle)) => cle,
+        _ => return Ok(authorize_url),
+    };
+
+    // aud est l'issuer de l'OP (4)
+    let issuer = decouverte::document(f)
+        .await
+        .and_then(|document| decouverte::champ(&document, "issuer").ok_or("issuer absent du document discovery".to_owned()))
+        .map_err(Erreur::FournisseurInjoignable)?;
+    let objet = objet_requete(&authorize_url, &issuer, &cle).map_err(|e| Erreur::AssertionClient(e.to_string()))?;
+
+    authorize_url.query_pairs_mut().append_pair("request", &objet);
+    Ok(authorize_url)
+}
+
+// Les claims reprennent les paramètres de la requête; iss est le client_id
+fn objet_requete(authorize_url: &Url, issuer: &str, cle: &CleClient) -> Result<String, jsonwebtoken::errors::Error> {
+    let mut claims = authorize_url
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), Value::String(v.into_owned())))
+        .collect::<Map<_, _>>();
+    let client_id = claims.get("client_id").cloned().unwrap_or_default();
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    claims.insert("iss".to_owned(), client_id);
+    claims.insert("aud".to_owned(), Value::from(issuer));
+    claims.insert("jti".to_owned(), Value::from(random_token(32)));
+    claims.insert("iat".to_owned(), Value::from(maintenant));
+    claims.insert("nbf".to_owned(), Value::from(maintenant));
+    claims.insert("exp".to_owned(), Value::from(maintenant + 300));
+
+    let mut entete = Header::new(cle.alg);
+    entete.typ = Some(TYPE_OBJET.to_owned());
+    entete.kid = cle.kid.clone();
+    encode(&entete, &claims, &cle.cle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, EncodingKey, Validation};
+
+    #[test]
+    fn objet_requete_signe() {
+        let cle = CleClient {
+            cle: EncodingKey::from_secret(b"LOL"),
+            alg: Algorithm::HS256,
+            kid: Some("k1".to_owned()),
+        };
+        let authorize_url = Url::parse("https://op.exemple.com/authorize?response_type=code&client_id=client&state=bWJq&scope=openid").unwrap();
+        let objet = objet_requete(&authorize_url, "https://op.exemple.com", &cle).unwrap();
+
+        let entete = decode_header(&objet).unwrap();
+        assert_eq!(entete.typ.as_deref(), Some(TYPE_OBJET));
+        assert_eq!(entete.kid.as_deref(), Some("k1"));
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&["https://op.exemple.com"]);
+        validation.set_issuer(&["client"]);
+        let claims = decode::<Map<String, Value>>(&objet, &DecodingKey::from_secret(b"LOL"), &validation)
+            .unwrap()
+            .claims;
+        assert_eq!(claims["response_type"], "code");
+        assert_eq!(claims["state"], "bWJq");
+        assert_eq!(claims["scope"], "openid");
+    }
+}
@@ -0,0 +1,91 @@
+use crate::decouverte;
+use crate::session::Fournisseur;
+use commun::jwt;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)
+const CLAIMS_JWT: [&str; 4] = ["iss", "aud", "exp", "iat"];
+
+// Paramètres de la réponse d'autorisation portés par le JWT response, signé par l'OP de la session (4.4)
+pub async fn valider(f: &Fournisseur, response: &str) -> Result<HashMap<String, String>, String> {
+    // Une réponse chiffrée (JWE) compte cinq parties
+    if response.split('.').count() != 3 {
+        return Err("la réponse doit être un JWT signé".to_owned());
+    }
+    let entete = decode_header(response).map_err(|e| e.to_string())?;
+    let (issuer, jwk) = decouverte::cle(f, entete.kid.as_deref().unwrap_or_default()).await?;
+    let alg = jwt::algorithme(&jwk, entete.alg)?;
+    let cle = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+    verifier(response, &cle, alg, &issuer, f.secrets().0.trim())
+}
+
+fn verifier(response: &str, cle: &DecodingKey, alg: Algorithm, issuer: &str, client_id: &str) -> Result<HashMap<String, String>, String> {
+    let mut validation = Validation::new(alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["iss", "aud", "exp"]);
+    let claims = decode::<Map<String, Value>>(response, cle, &validation)
+        .map_err(|e| e.to_string())?
+        .claims;
+
+    Ok(claims
+        .into_iter()
+        .filter(|(k, _)| !CLAIMS_JWT.contains(&k.as_str()))
+        .filter_map(|(k, v)| Some((k, v.as_str()?.to_owned())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn response(claims: Value) -> String {
+        encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(b"LOL")).unwrap()
+    }
+
+    fn claims() -> Value {
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        json!({
+            "iss": "https://op.exemple.com",
+            "aud": "client",
+            "exp": maintenant + 600,
+            "code": "PyyFaux2o7Q0YfXBU32jhw.5FXSQpvr8akv9CeRDSd0QA",
+            "state": "bWJq",
+        })
+    }
+
+    #[test]
+    fn reponse_valide() {
+        let cle = DecodingKey::from_secret(b"LOL");
+        let parametres = verifier(&response(claims()), &cle, Algorithm::HS256, "https://op.exemple.com", "client").unwrap();
+        assert_eq!(parametres["code"], "PyyFaux2o7Q0YfXBU32jhw.5FXSQpvr8akv9CeRDSd0QA");
+        assert_eq!(parametres["state"], "bWJq");
+        assert!(!parametres.contains_key("iss"));
+    }
+
+    #[test]
+    fn reponse_invalide() {
+        let cle = DecodingKey::from_secret(b"LOL");
+        let valider = |claims: Value| verifier(&response(claims), &cle, Algorithm::HS256, "https://op.exemple.com", "client");
+
+        let mut autre_client = claims();
+        autre_client["aud"] = json!("autre");
+        assert!(valider(autre_client).is_err());
+
+        let mut autre_op = claims();
+        autre_op["iss"] = json!("https://autre.exemple.com");
+        assert!(valider(autre_op).is_err());
+
+        let mut expiree = claims();
+        expiree["exp"] = json!(1);
+        assert!(valider(expiree).is_err());
+
+        let cle = DecodingKey::from_secret(b"BOUH");
+        assert!(verifier(&response(claims()), &cle, Algorithm::HS256, "https://op.exemple.com", "client").is_err());
+    }
+}
@@ -1,9 +1,34 @@
+mod cible;
+mod client;
+mod config;
+mod cookies;
+mod deconnexion;
+mod decouverte;
+mod dpop;
+mod entetes;
+mod erreur;
+mod jar;
+mod jarm;
+mod limites;
+mod magasin;
+mod metriques;
+mod par;
+mod persistance;
+mod sante;
 mod session;
+mod statique;
+use commun::jwt;
+pub use entetes::{CSP_DEFAUT, FRAME_OPTIONS_DEFAUT, PERMISSIONS_POLICY_DEFAUT, REFERRER_POLICY_DEFAUT};
 use lazy_static::lazy_static;
 use serde_json::{Map, Value};
-use session::{random_token, Session, SessionId};
+use session::{random_token, Demande, Session, SessionId};
+pub use statique::Fichier;
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio_rustls::rustls::sign::CertifiedKey;
 
 lazy_static! {
     static ref SESSIONS: Arc<RwLock<HashMap<SessionId, Session>>> = Arc::new(RwLock::new(HashMap::new()));
@@ -11,61 +36,280 @@ lazy_static! {
     static ref LOL_MAP: Map<String, Value> = Map::default();
 }
 
+pub fn configurer(chemin: &Path) -> Result<(), Box<dyn Error>> {
+    config::charger(chemin)
+}
+
+// La clé certifiée est celle que sert le listener TLS; son état est rapporté par /readyz
+pub fn configurer_tls(cert: &Path, cle: &Path) -> Result<CertifiedKey, Box<dyn Error>> {
+    sante::charger_tls(cert, cle)
+}
+
+// Avec au moins une clé, les sessions sont chiffrées dans le cookie Session-Id plutôt que gardées en mémoire
+pub fn configurer_cles_session(cles: &[String]) -> Result<(), Box<dyn Error>> {
+    magasin::configurer(cles)
+}
+
+pub fn sauvegarder_sessions(chemin: &Path) -> Result<usize, Box<dyn Error>> {
+    persistance::sauvegarder(&SESSIONS.read().expect("Failed due to poisoned lock"), chemin)
+}
+
+pub fn restaurer_sessions(chemin: &Path) -> Result<usize, Box<dyn Error>> {
+    persistance::restaurer(&mut SESSIONS.write().expect("Failed due to poisoned lock"), chemin)
+}
+
+pub fn configurer_origines(origines: &[String]) -> Result<(), Box<dyn Error>> {
+    config::definir_origines(origines)
+}
+
+// Origines que /api et /client_credentials peuvent appeler avec un jeton; sans configuration, les adresses publiques seulement
+pub fn configurer_origines_api(origines: &[String]) -> Result<(), Box<dyn Error>> {
+    config::definir_origines_api(origines)
+}
+
+// Cookies Secure et préfixés __Host- lorsque le serveur est servi en HTTPS
+pub fn configurer_cookies(securise: bool) {
+    cookies::configurer(securise)
+}
+
+// Requêtes par minute acceptées sur /userinfos, /auth et /backchannel-logout, par IP et par cookie de session; 0 désactive la limite
+pub fn configurer_limites(par_ip: u32, par_session: u32) {
+    limites::configurer(par_ip, par_session)
+}
+
+// Entêtes de sécurité ajoutés aux réponses par filters::securite; une valeur vide retire l'entête
+pub fn configurer_entetes(
+    csp: &str,
+    hsts_max_age: u64,
+    frame_options: &str,
+    referrer_policy: &str,
+    permissions_policy: &str,
+) -> Result<(), Box<dyn Error>> {
+    entetes::configurer(csp, hsts_max_age, frame_options, referrer_policy, permissions_policy)
+}
+
+// Durée de vie du jeton lorsque l'OP ne retourne pas expires_in
+pub fn configurer_sessions(expiration_defaut: Duration) {
+    config::definir_expiration_defaut(expiration_defaut)
+}
+
 pub mod filters {
 
     use super::*;
+    use crate::erreur::Erreur;
     use std::convert::Infallible;
+    use std::net::SocketAddr;
     use std::path::PathBuf;
-    use warp::filters::{cookie, header};
+    use warp::filters::header;
+    use warp::http::{Error, Response};
     use warp::Filter;
 
     pub fn static_file(path: PathBuf) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         warp::path("static").and(warp::fs::dir(path))
     }
 
+    // Fichiers compilés dans l'exécutable, servis sous /static comme ceux du répertoire
+    pub fn static_embarque(fichiers: &'static [Fichier]) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("static")
+            .and(warp::get().or(warp::head()).unify())
+            .and(warp::path::tail())
+            .and(header::optional::<String>("if-none-match"))
+            .and_then(move |chemin: warp::path::Tail, if_none_match: Option<String>| async move {
+                match fichiers.iter().find(|fichier| fichier.nom == chemin.as_str()) {
+                    Some(fichier) => Ok(statique::repondre(fichier, if_none_match)),
+                    None => Err(warp::reject::not_found()),
+                }
+            })
+    }
+
     pub fn userinfos() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let userinfos = cookie(cookies::nom_csrf)
+            .and(header::optional("X-Csrf-Token"))
+            .and(cookie(cookies::nom_session))
+            .and(json_body(4096))
+            .and(clone_sessions())
+            .and_then(handlers::userinfos);
         warp::path("userinfos")
             .and(warp::path::end())
             .and(warp::post())
-            .and(cookie::optional("Csrf-Token"))
-            .and(header::optional("X-Csrf-Token"))
-            .and(cookie::optional("Session-Id"))
-            .and(json_body())
-            .and(clone_sessions())
-            .and_then(handlers::userinfos)
+            .and(limite("userinfos", Erreur::probleme).or(userinfos))
     }
 
+    // Réponse d'autorisation en query (GET) ou postée par l'OP ou le relais du fragment (response_mode=form_post)
     pub fn auth() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let reponse = warp::get()
+            .and(warp::query::<HashMap<String, String>>())
+            .or(warp::post()
+                .and(warp::body::content_length_limit(16 * 1024))
+                .and(warp::body::form::<HashMap<String, String>>()))
+            .unify();
+        let auth = cookie(cookies::nom_session).and(reponse).and(clone_sessions()).and_then(handlers::auth);
         warp::path("auth")
+            .and(warp::path::end())
+            .and(warp::get().or(warp::post()).unify())
+            .and(limite("auth", Erreur::page).or(auth))
+    }
+
+    pub fn token() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("token")
             .and(warp::path::end())
             .and(warp::get())
-            .and(cookie::optional("Session-Id"))
-            .and(warp::query::<HashMap<String, String>>())
+            .and(cookie(cookies::nom_session))
             .and(clone_sessions())
-            .and_then(handlers::auth)
+            .and_then(handlers::token)
+    }
+
+    // Le jeton est obtenu avec le secret du serveur: réservé à une session authentifiée
+    pub fn client_credentials() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("client_credentials")
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(cookie(cookies::nom_csrf))
+            .and(header::optional("X-Csrf-Token"))
+            .and(cookie(cookies::nom_session))
+            .and(json_body(4096))
+            .and(clone_sessions())
+            .and_then(handlers::client_credentials)
+    }
+
+    pub fn api() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("api")
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(cookie(cookies::nom_csrf))
+            .and(header::optional("X-Csrf-Token"))
+            .and(cookie(cookies::nom_session))
+            .and(json_body(64 * 1024))
+            .and(clone_sessions())
+            .and_then(handlers::api)
+    }
+
+    // Appelée par l'OP (OpenID Connect Back-Channel Logout 1.0): ni cookie ni Csrf
+    pub fn backchannel_logout() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let backchannel_logout = warp::body::content_length_limit(16 * 1024)
+            .and(warp::body::form::<HashMap<String, String>>())
+            .and(clone_sessions())
+            .and_then(handlers::backchannel_logout);
+        warp::path("backchannel-logout")
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(limite("backchannel-logout", Erreur::probleme).or(backchannel_logout))
+            .map(|reply| warp::reply::with_header(reply, "Cache-Control", "no-store"))
+    }
+
+    // Affichée par l'OP dans une iframe (OpenID Connect Front-Channel Logout 1.0)
+    pub fn frontchannel_logout() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let frontchannel_logout = warp::query::<HashMap<String, String>>()
+            .and(clone_sessions())
+            .and_then(handlers::frontchannel_logout);
+        warp::path("frontchannel-logout")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(limite("frontchannel-logout", Erreur::page).or(frontchannel_logout))
+    }
+
+    pub fn statut() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("statut")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(cookie(cookies::nom_session))
+            .and(clone_sessions())
+            .map(handlers::statut)
+    }
+
+    pub fn metrics() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("metrics")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(clone_sessions())
+            .and_then(handlers::metrics)
+    }
+
+    pub fn healthz() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("healthz")
+            .and(warp::path::end())
+            .and(warp::get())
+            .map(|| warp::reply::json(&serde_json::json!({ "statut": "ok" })))
+    }
+
+    pub fn readyz() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path("readyz").and(warp::path::end()).and(warp::get()).and_then(handlers::readyz)
+    }
+
+    // CSP, HSTS, X-Frame-Options, Referrer-Policy et Permissions-Policy, à appliquer à l'ensemble des routes
+    pub fn securite(reply: impl warp::Reply) -> warp::reply::Response {
+        entetes::appliquer(reply)
+    }
+
+    // Le nom des cookies dépend de la configuration TLS, connue seulement au démarrage
+    fn cookie(nom: fn() -> &'static str) -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+        header::optional::<String>("cookie").map(move |entete: Option<String>| entete.and_then(|entete| cookies::valeur(&entete, nom())))
+    }
+
+    // Répond 429 avant la lecture du corps lorsque la limite est atteinte, sinon laisse passer la requête.
+    // Derrière le serveur TLS, l'adresse du client est portée par une extension de la requête.
+    fn limite(
+        route: &'static str,
+        reponse: fn(&Erreur) -> Result<Response<String>, Error>,
+    ) -> impl Filter<Extract = (Result<Response<String>, Error>,), Error = warp::Rejection> + Clone {
+        warp::addr::remote()
+            .and(warp::ext::optional::<SocketAddr>())
+            .and(cookie(cookies::nom_session))
+            .and_then(
+                move |remote: Option<SocketAddr>, tls: Option<SocketAddr>, session: Option<String>| async move {
+                    match limites::consommer(route, remote.or(tls).map(|adresse| adresse.ip()), session.as_deref()) {
+                        Ok(()) => Err(warp::reject()),
+                        Err(delai) => Ok(reponse(&Erreur::TropDeRequetes(delai))),
+                    }
+                },
+            )
     }
 
     fn clone_sessions() -> impl Filter<Extract = (Arc<RwLock<HashMap<SessionId, Session>>>,), Error = Infallible> + Clone {
         warp::any().map(move || SESSIONS.clone())
     }
 
-    fn json_body() -> impl Filter<Extract = (HashMap<String, String>,), Error = warp::Rejection> + Clone {
-        warp::body::content_length_limit(1024).and(warp::body::json())
+    fn json_body(limite: u64) -> impl Filter<Extract = (HashMap<String, String>,), Error = warp::Rejection> + Clone {
+        warp::body::content_length_limit(limite).and(warp::body::json())
     }
 }
 
 mod handlers {
-    use crate::session::Fournisseur;
+    use crate::client;
+    use crate::cookies::{self, SameSite};
+    use crate::deconnexion;
+    use crate::erreur::Erreur;
+    use crate::metriques::{
+        CONNEXIONS_COMPLETEES, CONNEXIONS_DEMARREES, DECONNEXIONS_BACKCHANNEL, ECHECS_ECHANGE_JETON, LATENCE_USERINFO, REJETS_CSRF,
+    };
+    use crate::session::{Fournisseur, Identite};
+    use futures_util::stream;
     use oauth2::reqwest::async_http_client;
-    use oauth2::{basic::BasicClient, AuthType, AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope, TokenUrl};
+    use oauth2::url::Url;
     use oauth2::{AuthorizationCode, TokenResponse};
+    use oauth2::{CsrfToken, RedirectUrl, Scope};
     use session::Token;
-    use std::time::Duration;
+    use std::time::Instant;
+    use tracing::{info, instrument, warn, Span};
+    use warp::sse::Event;
 
     use super::*;
     use std::convert::Infallible;
-    use warp::http::{Error, Response, StatusCode};
+    use warp::http::{Error, Method, Response, StatusCode};
+
+    const INTERVALLE_STATUT: Duration = Duration::from_secs(1);
+    // En deçà, la session est signalée expire_bientot
+    const EXPIRATION_PROCHE: Duration = Duration::from_secs(60);
+    // Page de retour de l'OP pour response_mode=fragment
+    const RELAIS_FRAGMENT: &str = r#"<!DOCTYPE html>
+<html lang="fr">
+<head><meta charset="utf-8"><title>Authentification</title><script src="/static/fragment.js" defer></script></head>
+<body></body>
+</html>
+"#;
 
+    // Les arguments ne sont pas tracés: ils contiennent les jetons Csrf et l'id de session
+    #[instrument(name = "userinfos", skip_all, fields(correlation = %random_token(16), fournisseur, session))]
     pub async fn userinfos(
         csrf_cookie: Option<String>,
         csrf_header: Option<String>,
@@ -73,196 +317,626 @@ mod handlers {
         body: HashMap<String, String>,
         sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
     ) -> Result<impl warp::Reply, Infallible> {
-        // Validation Csrf si le cookie Csrf est présent
-        if let Some(ctoken) = csrf_cookie {
-            match csrf_header {
-                Some(htoken) if htoken == ctoken => (),
-                Some(htoken) if htoken != ctoken => {
-                    eprintln!("{htoken} != {ctoken}");
-                    return Ok(reply_error(StatusCode::FORBIDDEN));
-                }
-                _ => {
-                    eprintln!("X-Csrf-Token est absent");
-                    return Ok(reply_error(StatusCode::FORBIDDEN));
-                }
-            }
-        };
+        if !csrf_valide(csrf_cookie, csrf_header) {
+            return Ok(Erreur::CsrfInvalide.probleme());
+        }
 
         let fournisseur = body.get("fournisseur").unwrap_or(&LOL);
-        let origine = body.get("origine").unwrap_or(&LOL);
+        let origine = match config::origine_autorisee(body.get("origine").unwrap_or(&LOL)) {
+            Ok(origine) => origine,
+            Err(e) => return Ok(e.probleme()),
+        };
+        let demande = Demande::from(&body);
+        Span::current().record("fournisseur", fournisseur.as_str());
+        // Le cookie SameSite=None du POST de l'OP doit être Secure: refusé par le navigateur en HTTP
+        if demande.form_post() && !cookies::securise() {
+            let erreur = Erreur::RequeteInvalide("response_mode=form_post exige que le serveur soit servi en HTTPS".to_owned());
+            return Ok(erreur.probleme());
+        }
 
-        let response = match session_cookie {
-            Some(stoken) => {
-                let id: SessionId = stoken.into();
-                // MutexGuard n'est pas Send
-                let session = sessions.read().expect("Failed due to poisoned lock").get(&id).cloned();
-                match session {
-                    Some(session) => {
-                        match session {
-                            session if session.is_expired() => {
-                                eprintln!("userinfos: session expirée");
-                                sessions.write().expect("Failed due to poisoned lock").remove(&id);
-                                reply_redirect_fournisseur(fournisseur, origine, sessions)
-                            }
-                            Session::Authenticated(f, token) if &f.to_string() == fournisseur => {
-                                let client = reqwest::Client::new();
-                                let response = match client.get(f.userinfos()).bearer_auth(token.secret()).send().await {
-                                    Ok(response) => response,
-                                    Err(e) => {
-                                        eprintln!("{e}");
-                                        return Ok(reply_error(StatusCode::INTERNAL_SERVER_ERROR));
-                                    }
-                                };
-                                let userinfo = response.json::<Value>().await.unwrap_or_default();
-                                let map = userinfo.as_object().unwrap_or(&LOL_MAP);
-                                let infos = map
-                                    .iter()
-                                    .map(|(k, v)| {
-                                        let mut map = serde_json::Map::new();
-                                        map.insert("propriété".into(), Value::String(k.to_owned()));
-                                        map.insert("valeur".into(), v.to_owned());
-                                        Value::Object(map)
-                                    })
-                                    .collect::<Vec<Value>>();
-
-                                Response::builder()
-                                    .status(StatusCode::OK)
-                                    .body(serde_json::to_string(&infos).unwrap_or_default())
-                            }
-                            _ => {
-                                // Changement de fournisseur
-                                sessions.write().expect("Failed due to poisoned lock").remove(&id);
-                                reply_redirect_fournisseur(fournisseur, origine, sessions)
+        let response =
+            match session_cookie {
+                Some(stoken) => {
+                    Span::current().record("session", masquer(&stoken));
+                    // MutexGuard n'est pas Send
+                    let session = magasin::charger(&sessions, &stoken);
+                    match session {
+                        Some(session) => {
+                            match session {
+                                session if session.is_expired() => {
+                                    info!(transition = "expirée -> supprimée", "session expirée");
+                                    magasin::retirer(&sessions, &stoken);
+                                    reply_redirect_fournisseur(fournisseur, &origine, demande, sessions).await
+                                }
+                                Session::Authenticated(f, token) if &f.to_string() == fournisseur && token.demande() == &demande => {
+                                    let client = reqwest::Client::new();
+                                    let debut = Instant::now();
+                                    let response =
+                                        match dpop::envoyer(|| client.get(f.userinfos()), &Method::GET, f.userinfos(), token.secret(), token.dpop())
+                                            .await
+                                        {
+                                            Ok(response) => response,
+                                            Err(e) => return Ok(Erreur::FournisseurInjoignable(e).probleme()),
+                                        };
+                                    info!(
+                                        statut = response.status().as_u16(),
+                                        duree_ms = debut.elapsed().as_millis() as u64,
+                                        "userinfo endpoint"
+                                    );
+                                    LATENCE_USERINFO
+                                        .with_label_values(&[&f.to_string()])
+                                        .observe(debut.elapsed().as_secs_f64());
+                                    let userinfo = response.json::<Value>().await.unwrap_or_default();
+                                    let infos = proprietes(userinfo.as_object().unwrap_or(&LOL_MAP));
+
+                                    Response::builder()
+                                        .status(StatusCode::OK)
+                                        .body(serde_json::to_string(&infos).unwrap_or_default())
+                                }
+                                _ => {
+                                    // Changement de fournisseur, de scopes ou de paramètres d'autorisation
+                                    info!(transition = "Authenticated -> supprimée", "demande modifiée");
+                                    magasin::retirer(&sessions, &stoken);
+                                    reply_redirect_fournisseur(fournisseur, &origine, demande, sessions).await
+                                }
                             }
                         }
+                        None => reply_redirect_fournisseur(fournisseur, &origine, demande, sessions).await,
                     }
-                    None => reply_redirect_fournisseur(fournisseur, origine, sessions),
                 }
+                None => reply_redirect_fournisseur(fournisseur, &origine, demande, sessions).await,
+            };
+
+        Ok(response)
+    }
+
+    pub async fn token(session_cookie: Option<String>, sessions: Arc<RwLock<HashMap<SessionId, Session>>>) -> Result<impl warp::Reply, Infallible> {
+        let session = match session_cookie {
+            Some(stoken) => magasin::charger(&sessions, &stoken),
+            None => None,
+        };
+
+        let (f, token) = match session {
+            Some(Session::Authenticated(f, token)) if !token.is_expired() => (f, token),
+            _ => return Ok(Erreur::NonAuthentifie.probleme()),
+        };
+
+        let mut map = Map::new();
+        map.insert("fournisseur".into(), Value::String(f.to_string()));
+        map.insert("expireDans".into(), Value::from(token.expire_dans().as_secs()));
+        if let Some(cle) = token.dpop() {
+            map.insert("jkt".into(), Value::String(cle.jkt()));
+        }
+        match jwt::inspecter(token.secret()) {
+            jwt::FormatJeton::Jwt { entete, charge } => {
+                // Liaison du jeton à la clé DPoP (RFC 9449 6.1)
+                if let Some(jkt) = charge.get("cnf").and_then(|cnf| cnf.get("jkt")) {
+                    map.insert("cnf.jkt".into(), jkt.clone());
+                }
+                map.insert("format".into(), Value::String("JWT".into()));
+                for claim in ["scp", "roles", "aud", "exp"] {
+                    map.insert(claim.into(), charge.get(claim).cloned().unwrap_or_default());
+                }
+                map.insert("entête".into(), Value::Object(entete));
+                map.insert("charge".into(), Value::Object(charge));
+            }
+            jwt::FormatJeton::Opaque => {
+                map.insert("format".into(), Value::String("opaque".into()));
             }
-            None => reply_redirect_fournisseur(fournisseur, origine, sessions),
+        }
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Value::Object(map).to_string()))
+    }
+
+    #[instrument(name = "backchannel_logout", skip_all, fields(correlation = %random_token(16), fournisseur))]
+    pub async fn backchannel_logout(
+        form: HashMap<String, String>,
+        sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        let logout_token = match form.get("logout_token") {
+            Some(logout_token) => logout_token,
+            None => return Ok(Erreur::RequeteInvalide("logout_token manquant".to_owned()).probleme()),
         };
 
-        Ok(response)
+        let deconnexion = match deconnexion::valider(logout_token).await {
+            Ok(deconnexion) => deconnexion,
+            Err(e) => return Ok(Erreur::LogoutTokenInvalide(e).probleme()),
+        };
+        let fournisseur = deconnexion.fournisseur.to_string();
+        Span::current().record("fournisseur", fournisseur.as_str());
+
+        let retirees = magasin::deconnecter(&sessions, &deconnexion.identite);
+        info!(sessions = retirees, sid = ?deconnexion.identite.sid, "déconnexion par l'OP");
+        DECONNEXIONS_BACKCHANNEL.with_label_values(&[&fournisseur]).inc();
+
+        Ok(Response::builder().status(StatusCode::OK).body(String::default()))
     }
 
-    fn reply_error(sc: StatusCode) -> Result<Response<String>, Error> {
-        Response::builder().status(sc).body(String::default())
+    // Les sessions visées par iss et sid sont retirées. iss et sid sont requis: dans l'iframe cross-site de l'OP,
+    // le navigateur n'envoie pas le cookie Session-Id, SameSite=Strict après /auth.
+    #[instrument(name = "frontchannel_logout", skip_all, fields(correlation = %random_token(16)))]
+    pub async fn frontchannel_logout(
+        params: HashMap<String, String>,
+        sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        let retirees = match (params.get("iss"), params.get("sid")) {
+            (Some(iss), Some(sid)) => {
+                let identite = Identite {
+                    iss: Some(iss.to_owned()),
+                    sub: None,
+                    sid: Some(sid.to_owned()),
+                };
+                magasin::deconnecter(&sessions, &identite)
+            }
+            _ => return Ok(Erreur::RequeteInvalide("iss et sid sont requis".to_owned()).page()),
+        };
+        info!(sessions = retirees, "déconnexion front-channel");
+
+        // frame-ancestors prévaut sur X-Frame-Options: seuls les OP peuvent afficher la page
+        let origines = Fournisseur::TOUS
+            .iter()
+            .filter_map(|f| Url::parse(f.endpoints().0).ok())
+            .map(|url| url.origin().ascii_serialization())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Content-Security-Policy", format!("default-src 'none'; frame-ancestors {origines}"))
+            .header("Cache-Control", "no-cache, no-store")
+            .header("Pragma", "no-cache")
+            .body(r#"<!DOCTYPE html><html lang="fr"><head><meta charset="utf-8"><title>Déconnexion</title></head><body></body></html>"#.to_owned()))
+    }
+
+    // État de la session poussé chaque seconde; le flux se termine lorsque la session est absente ou expirée
+    pub fn statut(session_cookie: Option<String>, sessions: Arc<RwLock<HashMap<SessionId, Session>>>) -> impl warp::Reply {
+        let flux = stream::unfold(Some(tokio::time::interval(INTERVALLE_STATUT)), move |intervalle| {
+            let session = session_cookie.as_deref().and_then(|stoken| magasin::charger(&sessions, stoken));
+            async move {
+                let mut intervalle = intervalle?;
+                intervalle.tick().await;
+                let (fin, statut) = statut_session(session);
+                let evenement = Event::default().event("statut").json_data(statut);
+                Some((evenement, if fin { None } else { Some(intervalle) }))
+            }
+        });
+        warp::sse::reply(warp::sse::keep_alive().stream(flux))
+    }
+
+    fn statut_session(session: Option<Session>) -> (bool, Value) {
+        match session {
+            None => (true, serde_json::json!({ "etat": "aucune" })),
+            Some(Session::AuthenticationRequested(f, ..)) => (false, serde_json::json!({ "etat": "en_attente", "fournisseur": f.to_string() })),
+            Some(Session::Authenticated(f, token)) => {
+                let expire_dans = token.expire_dans();
+                let etat = match expire_dans {
+                    d if d.is_zero() => "expiree",
+                    d if d <= EXPIRATION_PROCHE => "expire_bientot",
+                    _ => "authentifiee",
+                };
+                let statut = serde_json::json!({
+                    "etat": etat,
+                    "fournisseur": f.to_string(),
+                    "expireDans": expire_dans.as_secs(),
+                    "iss": token.identite().iss,
+                    "sid": token.identite().sid,
+                });
+                (expire_dans.is_zero(), statut)
+            }
+        }
+    }
+
+    pub async fn metrics(sessions: Arc<RwLock<HashMap<SessionId, Session>>>) -> Result<impl warp::Reply, Infallible> {
+        let metriques = metriques::exporter(&sessions.read().expect("Failed due to poisoned lock"));
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(metriques))
+    }
+
+    pub async fn readyz() -> Result<impl warp::Reply, Infallible> {
+        let (pret, detail) = sante::pret().await;
+        let statut = if pret { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+        Ok(Response::builder()
+            .status(statut)
+            .header("Content-Type", "application/json")
+            .body(detail.to_string()))
+    }
+
+    pub async fn client_credentials(
+        csrf_cookie: Option<String>,
+        csrf_header: Option<String>,
+        session_cookie: Option<String>,
+        body: HashMap<String, String>,
+        sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        if !csrf_exige(csrf_cookie, csrf_header) {
+            return Ok(Erreur::CsrfInvalide.probleme());
+        }
+
+        let session = match session_cookie {
+            Some(stoken) => magasin::charger(&sessions, &stoken),
+            None => None,
+        };
+        if !matches!(session, Some(Session::Authenticated(_, ref token)) if !token.is_expired()) {
+            return Ok(Erreur::NonAuthentifie.probleme());
+        }
+
+        // La ressource est vérifiée avant d'obtenir le jeton du client confidentiel
+        let cible = match body.get("url").filter(|url| !url.is_empty()) {
+            Some(url) => match cible::resoudre(url).await {
+                Ok(cible) => Some(cible),
+                Err(e) => return Ok(e.probleme()),
+            },
+            None => None,
+        };
+
+        let f: Fournisseur = body.get("fournisseur").unwrap_or(&LOL).as_str().into();
+        let assertion = match client::parametres_assertion(&f) {
+            Ok(assertion) => assertion,
+            Err(e) => return Ok(Erreur::AssertionClient(e.to_string()).probleme()),
+        };
+
+        let client = client::client_oauth(&f);
+        let scopes = body.get("scopes").unwrap_or(&LOL).split_whitespace().map(|s| Scope::new(s.to_owned()));
+        let requete = client.exchange_client_credentials().add_scopes(scopes);
+        let requete = match body.get("resource") {
+            Some(resource) if !resource.is_empty() => requete.add_extra_param("resource", resource),
+            _ => requete,
+        };
+        let requete = assertion.into_iter().fold(requete, |requete, (k, v)| requete.add_extra_param(k, v));
+
+        let token = match requete.request_async(async_http_client).await {
+            Ok(token) => token,
+            Err(e) => {
+                ECHECS_ECHANGE_JETON.with_label_values(&[&f.to_string()]).inc();
+                return Ok(Erreur::from(e).probleme());
+            }
+        };
+
+        let secret = token.access_token().secret();
+        let mut map = Map::new();
+        map.insert("token_type".into(), Value::String(token.token_type().as_ref().to_owned()));
+        if let Some(expires_in) = token.expires_in() {
+            map.insert("expires_in".into(), Value::from(expires_in.as_secs()));
+        }
+        if let Some(scopes) = token.scopes() {
+            map.insert(
+                "scope".into(),
+                Value::String(scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")),
+            );
+        }
+        match jwt::inspecter(secret) {
+            jwt::FormatJeton::Jwt { charge, .. } => {
+                map.insert("format".into(), Value::String("JWT".into()));
+                map.extend(charge);
+            }
+            jwt::FormatJeton::Opaque => {
+                map.insert("format".into(), Value::String("opaque".into()));
+            }
+        }
+
+        let mut reponse = Map::new();
+        reponse.insert("jeton".into(), Value::Array(proprietes(&map)));
+        if let Some(cible) = &cible {
+            reponse.insert("ressource".into(), appeler(Method::GET, cible, Vec::new(), None, secret, None).await);
+        }
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Value::Object(reponse).to_string()))
+    }
+
+    // Validation Csrf si le cookie Csrf est présent
+    fn csrf_valide(csrf_cookie: Option<String>, csrf_header: Option<String>) -> bool {
+        match (csrf_cookie, csrf_header) {
+            (None, _) => true,
+            (Some(ctoken), Some(htoken)) if htoken == ctoken => true,
+            (Some(_), Some(_)) => {
+                REJETS_CSRF.inc();
+                warn!("X-Csrf-Token est différent du cookie Csrf-Token");
+                false
+            }
+            (Some(_), None) => {
+                REJETS_CSRF.inc();
+                warn!("X-Csrf-Token est absent");
+                false
+            }
+        }
+    }
+
+    // Routes qui exigent une session: le cookie Csrf, émis avec la session, doit être présent (double soumission stricte)
+    fn csrf_exige(csrf_cookie: Option<String>, csrf_header: Option<String>) -> bool {
+        if csrf_cookie.is_none() {
+            REJETS_CSRF.inc();
+            warn!("cookie Csrf-Token absent");
+            return false;
+        }
+        csrf_valide(csrf_cookie, csrf_header)
+    }
+
+    // Assez pour corréler les traces d'une session sans exposer l'id
+    fn masquer(secret: &str) -> String {
+        format!("{}…", secret.chars().take(6).collect::<String>())
+    }
+
+    fn proprietes(map: &Map<String, Value>) -> Vec<Value> {
+        map.iter()
+            .map(|(k, v)| {
+                let mut map = serde_json::Map::new();
+                map.insert("propriété".into(), Value::String(k.to_owned()));
+                map.insert("valeur".into(), v.to_owned());
+                Value::Object(map)
+            })
+            .collect()
+    }
+
+    pub async fn api(
+        csrf_cookie: Option<String>,
+        csrf_header: Option<String>,
+        session_cookie: Option<String>,
+        body: HashMap<String, String>,
+        sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        if !csrf_exige(csrf_cookie, csrf_header) {
+            return Ok(Erreur::CsrfInvalide.probleme());
+        }
+
+        let session = match session_cookie {
+            Some(stoken) => magasin::charger(&sessions, &stoken),
+            None => None,
+        };
+        let token = match session {
+            Some(Session::Authenticated(_, token)) if !token.is_expired() => token,
+            _ => return Ok(Erreur::NonAuthentifie.probleme()),
+        };
+
+        let methode = match Method::from_bytes(body.get("methode").map_or("GET", |m| m.as_str()).to_uppercase().as_bytes()) {
+            Ok(methode) => methode,
+            Err(e) => return Ok(Erreur::RequeteInvalide(e.to_string()).probleme()),
+        };
+        let cible = match body.get("url") {
+            Some(url) if !url.is_empty() => match cible::resoudre(url).await {
+                Ok(cible) => cible,
+                Err(e) => return Ok(e.probleme()),
+            },
+            _ => return Ok(Erreur::RequeteInvalide("url manquant".to_owned()).probleme()),
+        };
+
+        // Une entête par ligne: Nom: valeur
+        let entetes = body
+            .get("entetes")
+            .unwrap_or(&LOL)
+            .lines()
+            .filter_map(|ligne| ligne.split_once(':'))
+            .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+            .collect();
+        let corps = body.get("corps").filter(|corps| !corps.is_empty()).cloned();
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(appeler(methode, &cible, entetes, corps, token.secret(), token.dpop()).await.to_string()))
     }
 
-    fn reply_redirect_fournisseur(
+    // Appel de la ressource protégée avec le jeton. Le corps est retourné tel quel s'il n'est pas JSON.
+    async fn appeler(
+        methode: Method,
+        cible: &cible::Cible,
+        entetes: Vec<(String, String)>,
+        corps: Option<String>,
+        secret: &str,
+        cle_dpop: Option<&dpop::CleDpop>,
+    ) -> Value {
+        let client = cible.client();
+        let url = cible.url.as_str();
+        let construire = || {
+            let requete = entetes
+                .iter()
+                .fold(client.request(methode.clone(), url), |requete, (k, v)| requete.header(k, v));
+            match &corps {
+                Some(corps) => requete.body(corps.clone()),
+                None => requete,
+            }
+        };
+
+        let mut ressource = Map::new();
+        let debut = Instant::now();
+        match dpop::envoyer(construire, &methode, url, secret, cle_dpop).await {
+            Ok(response) => {
+                info!(
+                    statut = response.status().as_u16(),
+                    duree_ms = debut.elapsed().as_millis() as u64,
+                    "ressource protégée"
+                );
+                ressource.insert("statut".into(), Value::from(response.status().as_u16()));
+                let entetes = response
+                    .headers()
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), Value::String(v.to_str().unwrap_or_default().to_owned())))
+                    .collect::<Map<String, Value>>();
+                ressource.insert("entêtes".into(), Value::Array(proprietes(&entetes)));
+                let corps = response.text().await.unwrap_or_default();
+                let corps = serde_json::from_str::<Value>(&corps).unwrap_or(Value::String(corps));
+                ressource.insert("corps".into(), corps);
+            }
+            Err(e) => {
+                warn!("{e}");
+                ressource.insert("erreur".into(), Value::String(e.to_string()));
+            }
+        }
+        Value::Object(ressource)
+    }
+
+    async fn reply_redirect_fournisseur(
         fournisseur: &str,
-        origine: &str,
+        origine: &Url,
+        demande: Demande,
         sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
     ) -> Result<Response<String>, Error> {
         let f: Fournisseur = fournisseur.into();
-        let (id, secret) = f.secrets();
-        let id = ClientId::new(id.to_owned());
-        let secret = ClientSecret::new(secret.to_owned());
-
-        let (url_auth, url_token) = f.endpoints();
-        let auth_url = AuthUrl::new(url_auth.to_owned()).unwrap();
-        let token_url = TokenUrl::new(url_token.to_owned()).unwrap();
-
-        let client = BasicClient::new(id, Some(secret), auth_url, Some(token_url))
-            .set_auth_type(AuthType::RequestBody)
-            .set_redirect_uri(RedirectUrl::new(origine.to_string() + "/auth").unwrap());
-
-        let (authorize_url, csrf_state) = client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new("openid".to_owned()))
-            .add_scope(Scope::new("email".to_owned()))
-            .add_scope(Scope::new("profile".to_owned()))
-            .url();
+        let redirection = origine.join("/auth").expect("origine validée");
+        let client = client::client_oauth(&f).set_redirect_uri(RedirectUrl::from_url(redirection));
 
-        let sessionid = SessionId::new();
-        let response = Response::builder()
-            .status(StatusCode::OK)
-            // Lax temporairement nécessaire pour l'envoi du cookie Session-Id avec le redirect par OP
-            .header("Set-Cookie", format!("Session-Id={0}; SameSite=Lax", sessionid.as_ref()))
-            .header("Set-Cookie", format!("Csrf-Token={0}; SameSite=Strict", random_token(64)))
-            .body(format!(r#"{{ "redirectOP": "{}" }}"#, authorize_url.as_str()));
+        let (authorize_url, csrf_state) = demande
+            .parametres
+            .iter()
+            .fold(
+                client
+                    .authorize_url(CsrfToken::new_random)
+                    .add_scopes(demande.scopes.iter().map(|s| Scope::new(s.to_owned()))),
+                |requete, (k, v)| requete.add_extra_param(k, v),
+            )
+            .url();
+        let authorize_url = match jar::signer(&f, authorize_url).await {
+            Ok(url) => url,
+            Err(e) => return e.probleme(),
+        };
+        let authorize_url = match par::redirection(&f, authorize_url).await {
+            Ok(url) => url,
+            Err(e) => return e.probleme(),
+        };
 
-        let session = Session::new(f, client, csrf_state);
-        sessions.write().expect("Failed due to poisoned lock").insert(sessionid, session);
+        // Le POST cross-site de l'OP ne porte le cookie Session-Id qu'avec SameSite=None
+        let same_site = if demande.form_post() { SameSite::None } else { SameSite::Lax };
+        CONNEXIONS_DEMARREES.with_label_values(&[&f.to_string()]).inc();
+        let session = Session::new(f, client, csrf_state, demande);
+        let sessionid = match magasin::enregistrer(&sessions, SessionId::new(), session) {
+            Ok(sessionid) => sessionid,
+            Err(e) => return e.probleme(),
+        };
+        info!(transition = "nouvelle -> AuthenticationRequested", nouvelle_session = %masquer(&sessionid), "redirection vers l'OP");
 
-        response
+        Response::builder()
+            .status(StatusCode::OK)
+            // Lax (None pour form_post) temporairement nécessaire pour l'envoi du cookie Session-Id au retour de l'OP
+            .header("Set-Cookie", cookies::session(&sessionid, same_site, cookies::DUREE_AUTORISATION))
+            .header("Set-Cookie", cookies::csrf(&random_token(64), cookies::DUREE_AUTORISATION))
+            .body(format!(r#"{{ "redirectOP": "{}" }}"#, authorize_url.as_str()))
     }
 
+    #[instrument(name = "auth", skip_all, fields(correlation = %random_token(16), fournisseur, session))]
     pub async fn auth(
         session_cookie: Option<String>,
         params: HashMap<String, String>,
         sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
     ) -> Result<impl warp::Reply, Infallible> {
+        // response_mode=fragment: le navigateur ne transmet pas le fragment, une page le poste à /auth
+        if params.is_empty() {
+            return Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/html; charset=utf-8")
+                .body(RELAIS_FRAGMENT.to_owned()));
+        }
+
         let response = match session_cookie {
             Some(stoken) => {
                 let id = SessionId::from(stoken);
-                let session = if let Some(session) = sessions.write().expect("Failed due to poisoned lock").remove(&id) {
+                Span::current().record("session", masquer(id.as_ref()));
+                let session = if let Some(session) = magasin::charger(&sessions, id.as_ref()) {
                     session
                 } else {
-                    eprintln!("auth: session inexistante");
-                    return Ok(reply_error(StatusCode::BAD_REQUEST));
+                    return Ok(Erreur::SessionInexistante.page());
                 };
 
-                let code = if let Some(code) = params.get("code") {
-                    code
-                } else {
-                    eprintln!("auth: auth code manquant");
-                    return Ok(reply_error(StatusCode::BAD_REQUEST));
+                let (f, client, csrf, demande) = match session {
+                    Session::AuthenticationRequested(ref f, ref c, ref csrf, ref d) => (f, c, csrf, d),
+                    _ => return Ok(Erreur::SessionDejaAuthentifiee.page()),
                 };
+                Span::current().record("fournisseur", f.to_string());
 
+                // JARM: les paramètres sont lus du JWT response. Une réponse en clair est refusée si JARM a été demandé.
+                let params = match params.get("response") {
+                    Some(response) => match jarm::valider(f, response).await {
+                        Ok(params) => params,
+                        Err(e) => return Ok(Erreur::ReponseAutorisationInvalide(e).page()),
+                    },
+                    None if demande.jarm() => return Ok(Erreur::ReponseAutorisationInvalide("paramètre response absent".to_owned()).page()),
+                    None => params,
+                };
+
+                // state est vérifié avant error (RFC 6749 4.1.2.1): une réponse forgée ne consomme pas la session
                 let state = if let Some(state) = params.get("state") {
                     state
                 } else {
-                    eprintln!("auth: csrf manquant");
-                    return Ok(reply_error(StatusCode::BAD_REQUEST));
-                };
-
-                let (client, csrf) = match session {
-                    Session::AuthenticationRequested(_, ref c, ref csrf) => (c, csrf),
-                    _ => {
-                        eprintln!("auth: session déjà authentifiée");
-                        return Ok(reply_error(StatusCode::BAD_REQUEST));
-                    }
+                    return Ok(Erreur::StateManquant.page());
                 };
 
                 if state != csrf.secret() {
-                    eprintln!("auth: csrf invalide");
-                    return Ok(reply_error(StatusCode::BAD_REQUEST));
+                    return Ok(Erreur::StateInvalide.page());
+                }
+
+                if magasin::retirer(&sessions, id.as_ref()).is_none() {
+                    return Ok(Erreur::SessionInexistante.page());
                 }
 
-                let token = match client
-                    .exchange_code(AuthorizationCode::new(code.to_owned()))
-                    .request_async(async_http_client)
-                    .await
-                {
+                // L'OP a refusé l'autorisation (RFC 6749 4.1.2.1)
+                if let Some(error) = params.get("error") {
+                    let erreur = Erreur::Fournisseur {
+                        error: error.to_owned(),
+                        description: params.get("error_description").cloned(),
+                    };
+                    return Ok(erreur.page());
+                }
+
+                let code = if let Some(code) = params.get("code") {
+                    code
+                } else {
+                    return Ok(Erreur::CodeManquant.page());
+                };
+
+                let assertion = match client::parametres_assertion(f) {
+                    Ok(assertion) => assertion,
+                    Err(e) => return Ok(Erreur::AssertionClient(e.to_string()).page()),
+                };
+
+                let requete = assertion
+                    .into_iter()
+                    .fold(client.exchange_code(AuthorizationCode::new(code.to_owned())), |requete, (k, v)| {
+                        requete.add_extra_param(k, v)
+                    });
+
+                // Clé éphémère propre à la session; le jeton obtenu y est lié
+                let cle_dpop = config::fournisseur(&f.to_string()).dpop.then(dpop::CleDpop::generer);
+                let debut = Instant::now();
+                let token = requete.request_async(|r| dpop::requete_jeton(cle_dpop.as_ref(), r)).await;
+                info!(duree_ms = debut.elapsed().as_millis() as u64, succes = token.is_ok(), "token endpoint");
+                let token = match token {
                     Ok(token) => token,
                     Err(e) => {
-                        eprintln!("{e}");
-                        return Ok(reply_error(StatusCode::BAD_REQUEST));
+                        ECHECS_ECHANGE_JETON.with_label_values(&[&f.to_string()]).inc();
+                        return Ok(Erreur::from(e).page());
                     }
                 };
 
-                let expired_in = token.expires_in().unwrap_or(Duration::from_secs(60));
-                let token = Token::new(token.access_token().to_owned(), expired_in);
+                let expired_in = token.expires_in().unwrap_or_else(config::expiration_defaut);
+                let identite = deconnexion::identite(token.extra_fields().id_token.as_deref());
+                let token = Token::new(token.access_token().to_owned(), expired_in, demande.clone())
+                    .identifier(identite)
+                    .lier(cle_dpop);
 
-                let response = Response::builder()
+                info!(transition = "AuthenticationRequested -> Authenticated", "session authentifiée");
+                CONNEXIONS_COMPLETEES.with_label_values(&[&f.to_string()]).inc();
+                let expire_dans = token.expire_dans();
+                let valeur = match magasin::enregistrer(&sessions, id, session.authentication_completed(token)) {
+                    Ok(valeur) => valeur,
+                    Err(e) => return Ok(e.page()),
+                };
+
+                Response::builder()
                     .status(StatusCode::FOUND)
                     .header("Location", "/static/userinfos.htm")
-                    // Après le redirect par OP, réécrire le cookie Session-Id avec Strict
-                    .header("Set-Cookie", format!("Session-Id={0}; SameSite=Strict", id.as_ref()))
-                    .body(String::default());
-
-                sessions
-                    .write()
-                    .expect("Failed due to poisoned lock")
-                    .insert(id, session.authentication_completed(token));
-
-                response
-            }
-            None => {
-                eprintln!("auth: Cookie de session inexistant");
-                reply_error(StatusCode::BAD_REQUEST)
+                    // Après le redirect par OP, réécrire le cookie Session-Id avec Strict pour la durée de vie du jeton.
+                    // Le cookie Csrf Strict n'est pas reçu au retour de l'OP: un nouveau jeton Csrf est émis.
+                    .header("Set-Cookie", cookies::session(&valeur, SameSite::Strict, expire_dans))
+                    .header("Set-Cookie", cookies::csrf(&random_token(64), expire_dans))
+                    .body(String::default())
             }
+            None => Erreur::CookieSessionInexistant.page(),
         };
 
         Ok(response)
@@ -275,6 +949,7 @@ mod tests {
     use std::path::PathBuf;
     use warp::http::StatusCode;
     use warp::test::request;
+    use warp::Filter;
 
     #[tokio::test]
     async fn static_file() {
@@ -286,6 +961,34 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn static_embarque() {
+        static FICHIERS: &[Fichier] = &[Fichier {
+            nom: "userinfos.htm",
+            contenu: b"<html></html>",
+            etag: r#""LOL""#,
+        }];
+        let filter = filters::static_embarque(FICHIERS);
+
+        let resp = request().method("GET").path("/static/userinfos.htm").reply(&filter).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Type"], "text/html");
+        assert_eq!(resp.headers()["ETag"], r#""LOL""#);
+        assert_eq!(resp.headers()["Cache-Control"], "no-cache");
+        assert_eq!(resp.body(), "<html></html>");
+
+        let resp = request()
+            .method("GET")
+            .path("/static/userinfos.htm")
+            .header("If-None-Match", r#""LOL""#)
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let resp = request().method("GET").path("/static/bouh.js").reply(&filter).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn csrf_mismatch1() {
         let resp = request()
@@ -323,9 +1026,548 @@ mod tests {
         assert!(resp.body().starts_with(b"{ \"redirectOP\": \"https://"));
     }
 
+    #[tokio::test]
+    async fn scopes_parametres() {
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .body(r#"{"fournisseur": "Google", "origine": "http://localhost", "scopes": "openid email", "prompt": "consent", "lol": "bouh"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8_lossy(resp.body());
+        assert!(body.contains("scope=openid+email&"));
+        assert!(body.contains("prompt=consent"));
+        assert!(!body.contains("lol"));
+    }
+
+    #[tokio::test]
+    async fn cookies_redirection() {
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .body(r#"{"fournisseur": "Google", "origine": "http://localhost"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        let cookies = resp
+            .headers()
+            .get_all("Set-Cookie")
+            .iter()
+            .map(|c| c.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(cookies.len(), 2);
+        assert!(cookies[0].starts_with("Session-Id="));
+        assert!(cookies[0].ends_with("; Path=/; Max-Age=600; SameSite=Lax; HttpOnly"));
+        assert!(cookies[1].starts_with("Csrf-Token="));
+        assert!(cookies[1].ends_with("; Path=/; Max-Age=600; SameSite=Strict"));
+    }
+
+    #[tokio::test]
+    async fn form_post_http() {
+        for response_mode in ["form_post", "form_post.jwt"] {
+            let resp = request()
+                .method("POST")
+                .path("/userinfos")
+                .body(format!(
+                    r#"{{"fournisseur": "Google", "origine": "http://localhost", "response_mode": "{response_mode}"}}"#
+                ))
+                .reply(&filters::userinfos())
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+            assert_eq!(probleme["code"], "requete_invalide");
+            assert!(resp.headers().get("Set-Cookie").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn origine_non_autorisee() {
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .body(r#"{"fournisseur": "Google", "origine": "https://lol.example.com"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["code"], "origine_non_autorisee");
+    }
+
+    #[tokio::test]
+    async fn origine_invalide() {
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .body(r#"{"fournisseur": "Google", "origine": "http://localhost/lol?bouh"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["code"], "origine_invalide");
+    }
+
+    #[test]
+    fn config_private_key_jwt_sans_cle() {
+        let chemin = std::env::temp_dir().join("test-oidc-config-sans-cle.json");
+        std::fs::write(&chemin, r#"{ "Microsoft": { "methode_auth": "private_key_jwt" } }"#).unwrap();
+        assert!(configurer(&chemin).is_err());
+    }
+
+    #[tokio::test]
+    async fn csrf_mismatch_probleme() {
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .header("Cookie", "Csrf-Token=LOL")
+            .body(r#"{"fournisseur": "Google", "origine": "http://localhost"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        assert_eq!(resp.headers()["Content-Type"], "application/problem+json");
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["status"], 403);
+        assert_eq!(probleme["code"], "csrf_invalide");
+    }
+
+    #[tokio::test]
+    async fn auth_erreur_fournisseur() {
+        let id = SessionId::new();
+        let client = client::ClientOidc::new(
+            oauth2::ClientId::new("LOL".to_owned()),
+            None,
+            oauth2::AuthUrl::new("http://localhost/authorize".to_owned()).unwrap(),
+            None,
+        );
+        let csrf = oauth2::CsrfToken::new_random();
+        let state = csrf.secret().to_owned();
+        let session = Session::new(session::Fournisseur::Google, client, csrf, Demande::from(&HashMap::new()));
+        let cookie = format!("Session-Id={}", id.as_ref());
+        SESSIONS.write().unwrap().insert(id, session);
+
+        let resp = request()
+            .method("GET")
+            .path(&format!(
+                "/auth?error=access_denied&error_description=Refus%20de%20l%27utilisateur&state={state}"
+            ))
+            .header("Cookie", cookie)
+            .reply(&filters::auth())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Content-Type"], "text/html; charset=utf-8");
+        let page = String::from_utf8_lossy(resp.body());
+        assert!(page.contains("access_denied: Refus de l'utilisateur"));
+    }
+
+    // Session en attente du retour de l'OP; retourne l'entête Cookie et le state attendu
+    fn session_autorisation(demande: Demande) -> (String, String) {
+        let id = SessionId::new();
+        let client = client::ClientOidc::new(
+            oauth2::ClientId::new("LOL".to_owned()),
+            None,
+            oauth2::AuthUrl::new("http://localhost/authorize".to_owned()).unwrap(),
+            None,
+        );
+        let csrf = oauth2::CsrfToken::new_random();
+        let state = csrf.secret().to_owned();
+        let session = Session::new(session::Fournisseur::Google, client, csrf, demande);
+        let cookie = format!("Session-Id={}", id.as_ref());
+        SESSIONS.write().unwrap().insert(id, session);
+        (cookie, state)
+    }
+
+    #[tokio::test]
+    async fn auth_form_post() {
+        let (cookie, state) = session_autorisation(Demande::from(&HashMap::new()));
+        let resp = request()
+            .method("POST")
+            .path("/auth")
+            .header("Cookie", cookie)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!(
+                "error=access_denied&error_description=Refus%20de%20l%27utilisateur&state={state}"
+            ))
+            .reply(&filters::auth())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let page = String::from_utf8_lossy(resp.body());
+        assert!(page.contains("access_denied: Refus de l'utilisateur"));
+    }
+
+    #[tokio::test]
+    async fn auth_erreur_forgee() {
+        let (cookie, _) = session_autorisation(Demande::from(&HashMap::new()));
+        // La session n'est pas consommée: la seconde réponse forgée est refusée pour la même raison
+        for _ in 0..2 {
+            let resp = request()
+                .method("GET")
+                .path("/auth?error=access_denied&state=BOUH")
+                .header("Cookie", &cookie)
+                .reply(&filters::auth())
+                .await;
+            assert!(String::from_utf8_lossy(resp.body()).contains("state_invalide"));
+        }
+    }
+
+    #[tokio::test]
+    async fn auth_relais_fragment() {
+        let resp = request().method("GET").path("/auth").reply(&filters::auth()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Type"], "text/html; charset=utf-8");
+        assert!(String::from_utf8_lossy(resp.body()).contains(r#"<script src="/static/fragment.js" defer>"#));
+    }
+
+    #[tokio::test]
+    async fn auth_jarm_sans_response() {
+        let body = HashMap::from([("response_mode".to_owned(), "query.jwt".to_owned())]);
+        let (cookie, _) = session_autorisation(Demande::from(&body));
+        let resp = request()
+            .method("GET")
+            .path("/auth?code=LOL&state=BOUH")
+            .header("Cookie", cookie)
+            .reply(&filters::auth())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(String::from_utf8_lossy(resp.body()).contains("reponse_autorisation_invalide"));
+    }
+
+    #[tokio::test]
+    async fn client_credentials_csrf_mismatch() {
+        let resp = request()
+            .method("POST")
+            .path("/client_credentials")
+            .header("Cookie", "Csrf-Token=LOL")
+            .header("X-Csrf-Token", "BOUH!")
+            .body(r#"{"fournisseur": "Microsoft", "scopes": "https://graph.microsoft.com/.default"}"#)
+            .reply(&filters::client_credentials())
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_sans_csrf() {
+        let resp = request()
+            .method("POST")
+            .path("/client_credentials")
+            .body(r#"{"fournisseur": "Microsoft", "scopes": "https://graph.microsoft.com/.default", "url": "http://localhost/"}"#)
+            .reply(&filters::client_credentials())
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_no_session() {
+        let resp = request()
+            .method("POST")
+            .path("/client_credentials")
+            .header("Cookie", "Csrf-Token=LOL")
+            .header("X-Csrf-Token", "LOL")
+            .body(r#"{"fournisseur": "Microsoft", "scopes": "https://graph.microsoft.com/.default"}"#)
+            .reply(&filters::client_credentials())
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn client_credentials_cible_non_autorisee() {
+        let id = SessionId::new();
+        let token = session::Token::new(
+            oauth2::AccessToken::new("LOL".to_owned()),
+            Duration::from_secs(3600),
+            Demande::from(&HashMap::new()),
+        );
+        let cookie = format!("Session-Id={}; Csrf-Token=LOL", id.as_ref());
+        SESSIONS
+            .write()
+            .unwrap()
+            .insert(id, Session::Authenticated(session::Fournisseur::Microsoft, token));
+
+        // Refusée avant l'échange: le jeton du client n'est pas obtenu
+        let resp = request()
+            .method("POST")
+            .path("/client_credentials")
+            .header("Cookie", cookie)
+            .header("X-Csrf-Token", "LOL")
+            .body(r#"{"fournisseur": "Microsoft", "scopes": "https://graph.microsoft.com/.default", "url": "http://10.0.0.1/admin"}"#)
+            .reply(&filters::client_credentials())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["code"], "cible_non_autorisee");
+    }
+
+    #[tokio::test]
+    async fn api_no_session() {
+        let resp = request()
+            .method("POST")
+            .path("/api")
+            .header("Cookie", "Session-Id=LOL; Csrf-Token=LOL")
+            .header("X-Csrf-Token", "LOL")
+            .body(r#"{"methode": "GET", "url": "https://graph.microsoft.com/v1.0/me"}"#)
+            .reply(&filters::api())
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn api_sans_csrf() {
+        let resp = request()
+            .method("POST")
+            .path("/api")
+            .header("Cookie", "Session-Id=LOL")
+            .body(r#"{"methode": "DELETE", "url": "https://graph.microsoft.com/v1.0/me"}"#)
+            .reply(&filters::api())
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn api_cible_non_autorisee() {
+        let id = SessionId::new();
+        let token = session::Token::new(
+            oauth2::AccessToken::new("LOL".to_owned()),
+            Duration::from_secs(3600),
+            Demande::from(&HashMap::new()),
+        );
+        let cookie = format!("Session-Id={}; Csrf-Token=LOL", id.as_ref());
+        SESSIONS
+            .write()
+            .unwrap()
+            .insert(id, Session::Authenticated(session::Fournisseur::Microsoft, token));
+
+        // Service de métadonnées cloud: le jeton ne doit pas lui être envoyé
+        let resp = request()
+            .method("POST")
+            .path("/api")
+            .header("Cookie", cookie)
+            .header("X-Csrf-Token", "LOL")
+            .body(r#"{"methode": "GET", "url": "http://169.254.169.254/latest/meta-data/"}"#)
+            .reply(&filters::api())
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Content-Type"], "application/problem+json");
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["code"], "cible_non_autorisee");
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        let _ = request()
+            .method("POST")
+            .path("/userinfos")
+            .header("Cookie", "Csrf-Token=LOL")
+            .body(r#"{"fournisseur": "Google", "origine": "http://localhost"}"#)
+            .reply(&filters::userinfos())
+            .await;
+        let resp = request().method("GET").path("/metrics").reply(&filters::metrics()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = String::from_utf8_lossy(resp.body());
+        assert!(body.contains("test_oidc_rejets_csrf_total"));
+        assert!(body.contains(r#"test_oidc_sessions{etat="Authenticated"}"#));
+        // Séries créées à zéro pour chaque fournisseur
+        assert!(body.contains(r#"test_oidc_deconnexions_backchannel_total{fournisseur="Microsoft"}"#));
+        assert!(body.contains(r#"test_oidc_latence_userinfo_secondes_count{fournisseur="Google"}"#));
+    }
+
+    #[tokio::test]
+    async fn healthz() {
+        let resp = request().method("GET").path("/healthz").reply(&filters::healthz()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body().as_ref(), br#"{"statut":"ok"}"#);
+    }
+
+    #[tokio::test]
+    async fn entetes_securite() {
+        let resp = request()
+            .method("GET")
+            .path("/healthz")
+            .reply(&filters::healthz().map(filters::securite))
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Security-Policy"], CSP_DEFAUT);
+        assert!(!CSP_DEFAUT.contains("'unsafe-"));
+        assert_eq!(resp.headers()["X-Frame-Options"], "DENY");
+        assert_eq!(resp.headers()["Referrer-Policy"], "no-referrer");
+        assert!(resp.headers().contains_key("Permissions-Policy"));
+        // Sans TLS
+        assert!(!resp.headers().contains_key("Strict-Transport-Security"));
+    }
+
+    #[tokio::test]
+    async fn backchannel_logout_invalide() {
+        let filter = filters::backchannel_logout();
+        let resp = request()
+            .method("POST")
+            .path("/backchannel-logout")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("state=LOL")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Cache-Control"], "no-store");
+
+        let resp = request()
+            .method("POST")
+            .path("/backchannel-logout")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("logout_token=LOL")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let probleme: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(probleme["code"], "logout_token_invalide");
+    }
+
+    #[tokio::test]
+    async fn frontchannel_logout() {
+        let id = SessionId::new();
+        let identite = session::Identite {
+            iss: Some("https://op.exemple.com".to_owned()),
+            sub: Some("LOL".to_owned()),
+            sid: Some("BOUH".to_owned()),
+        };
+        let token = session::Token::new(
+            oauth2::AccessToken::new("LOL".to_owned()),
+            Duration::from_secs(3600),
+            Demande::from(&HashMap::new()),
+        );
+        SESSIONS.write().unwrap().insert(
+            SessionId::from(id.as_ref().to_owned()),
+            Session::Authenticated(session::Fournisseur::Google, token.identifier(identite)),
+        );
+
+        let filter = filters::frontchannel_logout();
+        let resp = request()
+            .method("GET")
+            .path("/frontchannel-logout?iss=https%3A%2F%2Fop.exemple.com&sid=BOUH")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Cache-Control"], "no-cache, no-store");
+        assert!(resp.headers()["Content-Security-Policy"]
+            .to_str()
+            .unwrap()
+            .contains("https://accounts.google.com"));
+        assert!(!SESSIONS.read().unwrap().contains_key(&id));
+
+        let resp = request().method("GET").path("/frontchannel-logout?sid=BOUH").reply(&filter).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = request()
+            .method("GET")
+            .path("/frontchannel-logout")
+            .header("Cookie", format!("Session-Id={}", id.as_ref()))
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn statut_sans_session() {
+        let resp = request()
+            .method("GET")
+            .path("/statut")
+            .header("Cookie", "Session-Id=LOL")
+            .reply(&filters::statut())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Type"], "text/event-stream");
+        let corps = String::from_utf8_lossy(resp.body());
+        assert!(corps.starts_with("event:statut\ndata:"));
+        assert!(corps.contains(r#""etat":"aucune""#));
+    }
+
+    #[tokio::test]
+    async fn statut_expiree() {
+        let id = SessionId::new();
+        let token = session::Token::new(oauth2::AccessToken::new("LOL".to_owned()), Duration::ZERO, Demande::from(&HashMap::new()));
+        let cookie = format!("Session-Id={}", id.as_ref());
+        SESSIONS
+            .write()
+            .unwrap()
+            .insert(id, Session::Authenticated(session::Fournisseur::Microsoft, token));
+
+        let resp = request()
+            .method("GET")
+            .path("/statut")
+            .header("Cookie", cookie)
+            .reply(&filters::statut())
+            .await;
+        let corps = String::from_utf8_lossy(resp.body());
+        assert!(corps.contains(r#""etat":"expiree""#));
+        assert!(corps.contains(r#""expireDans":0"#));
+    }
+
     #[tokio::test]
     async fn no_session_cookie2() {
         let resp = request().method("GET").path("/auth?code=LOL").reply(&filters::auth()).await;
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn token_no_session() {
+        let resp = request()
+            .method("GET")
+            .path("/token")
+            .header("Cookie", "Session-Id=LOL")
+            .reply(&filters::token())
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn limite_ip() {
+        let filter = filters::userinfos();
+        for _ in 0..limites::PAR_IP_DEFAUT {
+            let resp = request()
+                .method("POST")
+                .path("/userinfos")
+                .remote_addr("192.0.2.41:4041".parse().unwrap())
+                .body("{}")
+                .reply(&filter)
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .remote_addr("192.0.2.41:4042".parse().unwrap())
+            .body("{}")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()["Content-Type"], "application/problem+json");
+        assert!(resp.headers()["Retry-After"].to_str().unwrap().parse::<u64>().unwrap() >= 1);
+
+        // Une autre IP n'est pas concernée
+        let resp = request()
+            .method("POST")
+            .path("/userinfos")
+            .remote_addr("192.0.2.42:4041".parse().unwrap())
+            .body("{}")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn limite_session() {
+        let filter = filters::auth();
+        for _ in 0..limites::PAR_SESSION_DEFAUT {
+            let resp = request()
+                .method("GET")
+                .path("/auth?code=LOL&state=LOL")
+                .header("Cookie", "Session-Id=LIMITE")
+                .reply(&filter)
+                .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let resp = request()
+            .method("GET")
+            .path("/auth?code=LOL&state=LOL")
+            .header("Cookie", "Session-Id=LIMITE")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key("Retry-After"));
+    }
 }
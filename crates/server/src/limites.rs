@@ -0,0 +1,159 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+// Requêtes par minute, 0 désactivant la limite
+pub const PAR_IP_DEFAUT: u32 = 120;
+pub const PAR_SESSION_DEFAUT: u32 = 30;
+
+// Nombre maximal de seaux. Les seaux pleins sont alors oubliés (ils se comportent comme des seaux neufs),
+// au plus une fois par seconde; si aucun ne l'est, une nouvelle clé est refusée.
+const MAX_SEAUX: usize = 10_000;
+const INTERVALLE_NETTOYAGE: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    static ref LIMITES: RwLock<(u32, u32)> = RwLock::new((PAR_IP_DEFAUT, PAR_SESSION_DEFAUT));
+    static ref SEAUX: Mutex<Seaux> = Mutex::new(Seaux::new(MAX_SEAUX));
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum Cle {
+    Ip(IpAddr),
+    Session(String),
+}
+
+// Seau à jetons: la capacité est rechargée en une minute
+struct Seau {
+    jetons: f64,
+    instant: Instant,
+}
+
+impl Seau {
+    fn new(capacite: u32, maintenant: Instant) -> Self {
+        Seau {
+            jetons: capacite as f64,
+            instant: maintenant,
+        }
+    }
+
+    fn consommer(&mut self, capacite: u32, maintenant: Instant) -> Result<(), Duration> {
+        let debit = capacite as f64 / 60.0;
+        self.jetons = (self.jetons + maintenant.duration_since(self.instant).as_secs_f64() * debit).min(capacite as f64);
+        self.instant = maintenant;
+        if self.jetons >= 1.0 {
+            self.jetons -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.jetons) / debit))
+        }
+    }
+
+    fn plein(&self, maintenant: Instant) -> bool {
+        maintenant.duration_since(self.instant) >= Duration::from_secs(60)
+    }
+}
+
+struct Seaux {
+    seaux: HashMap<(&'static str, Cle), Seau>,
+    max: usize,
+    nettoyage: Option<Instant>,
+}
+
+impl Seaux {
+    fn new(max: usize) -> Self {
+        Seaux {
+            seaux: HashMap::new(),
+            max,
+            nettoyage: None,
+        }
+    }
+
+    // Le seau de la session n'est pas créé ni consommé quand celui de l'IP refuse la requête
+    fn consommer(&mut self, route: &'static str, cles: [Option<(Cle, u32)>; 2], maintenant: Instant) -> Result<(), Duration> {
+        for (cle, capacite) in cles.into_iter().flatten() {
+            let cle = (route, cle);
+            if !self.seaux.contains_key(&cle) && !self.place(maintenant) {
+                return Err(INTERVALLE_NETTOYAGE);
+            }
+            self.seaux
+                .entry(cle)
+                .or_insert_with(|| Seau::new(capacite, maintenant))
+                .consommer(capacite, maintenant)?;
+        }
+        Ok(())
+    }
+
+    fn place(&mut self, maintenant: Instant) -> bool {
+        if self.seaux.len() < self.max {
+            return true;
+        }
+        if self
+            .nettoyage
+            .is_none_or(|nettoyage| maintenant.duration_since(nettoyage) >= INTERVALLE_NETTOYAGE)
+        {
+            self.nettoyage = Some(maintenant);
+            self.seaux.retain(|_, seau| !seau.plein(maintenant));
+        }
+        self.seaux.len() < self.max
+    }
+}
+
+pub fn configurer(par_ip: u32, par_session: u32) {
+    *LIMITES.write().expect("Failed due to poisoned lock") = (par_ip, par_session);
+}
+
+// Un jeton est consommé dans le seau de l'IP et dans celui de la session; l'erreur donne le délai avant le prochain jeton
+pub fn consommer(route: &'static str, ip: Option<IpAddr>, session: Option<&str>) -> Result<(), Duration> {
+    let (par_ip, par_session) = *LIMITES.read().expect("Failed due to poisoned lock");
+    let cles = [
+        ip.filter(|_| par_ip > 0).map(|ip| (Cle::Ip(ip), par_ip)),
+        session.filter(|_| par_session > 0).map(|s| (Cle::Session(s.to_owned()), par_session)),
+    ];
+    SEAUX.lock().expect("Failed due to poisoned lock").consommer(route, cles, Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recharge() {
+        let debut = Instant::now();
+        let mut seau = Seau::new(2, debut);
+        assert!(seau.consommer(2, debut).is_ok());
+        assert!(seau.consommer(2, debut).is_ok());
+        let delai = seau.consommer(2, debut).unwrap_err();
+        assert!((delai.as_secs_f64() - 30.0).abs() < 0.001);
+
+        // Un jeton toutes les 30 secondes
+        assert!(seau.consommer(2, debut + Duration::from_secs(31)).is_ok());
+        assert!(seau.consommer(2, debut + Duration::from_secs(40)).is_err());
+        assert!(seau.plein(debut + Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn plafond() {
+        let debut = Instant::now();
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut seaux = Seaux::new(3);
+        let session = |s: &str| Some((Cle::Session(s.to_owned()), 30));
+
+        // L'IP refuse: le seau de la session n'est pas créé
+        assert!(seaux.consommer("test", [Some((Cle::Ip(ip), 1)), None], debut).is_ok());
+        assert!(seaux.consommer("test", [Some((Cle::Ip(ip), 1)), session("LOL")], debut).is_err());
+        assert_eq!(seaux.seaux.len(), 1);
+
+        assert!(seaux.consommer("test", [None, session("LOL")], debut).is_ok());
+        assert!(seaux.consommer("test", [None, session("BOUH")], debut).is_ok());
+        // Plafond atteint et aucun seau plein: la nouvelle clé est refusée, une clé connue est acceptée
+        assert!(seaux.consommer("test", [None, session("MDR")], debut).is_err());
+        assert!(seaux.consommer("test", [None, session("LOL")], debut).is_ok());
+        assert_eq!(seaux.seaux.len(), 3);
+
+        // Une minute plus tard, les seaux pleins sont oubliés
+        assert!(seaux.consommer("test", [None, session("MDR")], debut + Duration::from_secs(61)).is_ok());
+        assert_eq!(seaux.seaux.len(), 1);
+    }
+}
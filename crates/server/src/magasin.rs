@@ -0,0 +1,345 @@
+use crate::client;
+use crate::cookies;
+use crate::dpop::CleDpop;
+use crate::erreur::Erreur;
+use crate::session::{Demande, Fournisseur, Identite, Session, SessionId, Token};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use lazy_static::lazy_static;
+use oauth2::{AccessToken, CsrfToken, RedirectUrl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Le chiffré est lié à son usage
+const AAD: &[u8] = b"test-oidc Session-Id";
+const TAILLE_NONCE: usize = 12;
+// Une déconnexion par l'OP est retenue au-delà de la durée de vie usuelle d'un jeton
+const RETENTION_REVOCATIONS: u64 = 24 * 3600;
+// Au-delà, la plus ancienne déconnexion retenue est oubliée
+const MAX_REVOCATIONS: usize = 10_000;
+// Taille maximale d'un cookie (nom et valeur) acceptée par les navigateurs (RFC 6265 6.1)
+const TAILLE_MAX_COOKIE: usize = 4096;
+
+lazy_static! {
+    // Sans clé, les sessions sont gardées en mémoire et le cookie ne contient que l'id.
+    // La première clé chiffre; toutes les clés déchiffrent, ce qui permet la rotation.
+    static ref CLES: RwLock<Vec<(String, Aes256Gcm)>> = RwLock::new(Vec::new());
+    // Une session sans état ne peut être retirée: les sessions authentifiées avant la déconnexion sont refusées.
+    // Une même identité (iss, sid) n'est retenue qu'une fois, avec sa dernière déconnexion.
+    static ref REVOCATIONS: RwLock<HashMap<Identite, u64>> = RwLock::new(HashMap::new());
+}
+
+// Session telle que scellée dans le cookie. Le client oauth2 est reconstruit à partir de l'URI de redirection.
+#[derive(Serialize, Deserialize)]
+enum Scellee {
+    Demandee {
+        fournisseur: String,
+        csrf: String,
+        redirection: String,
+        demande: Demande,
+        // Un cookie capturé n'est plus accepté au-delà du délai accordé pour s'authentifier
+        #[serde(default)]
+        emission: u64,
+    },
+    Authentifiee {
+        fournisseur: String,
+        jeton: String,
+        expiration: u64,
+        demande: Demande,
+        #[serde(default)]
+        identite: Identite,
+        #[serde(default)]
+        authentification: u64,
+        #[serde(default)]
+        dpop: Option<CleDpop>,
+    },
+}
+
+pub fn configurer(cles: &[String]) -> Result<(), Box<dyn Error>> {
+    *CLES.write().expect("Failed due to poisoned lock") = analyser_cles(cles)?;
+    Ok(())
+}
+
+// Chaque clé est donnée sous la forme id:clé, la clé AES-256 étant encodée en base64
+fn analyser_cles(cles: &[String]) -> Result<Vec<(String, Aes256Gcm)>, Box<dyn Error>> {
+    cles.iter()
+        .map(|cle| {
+            let (id, cle) = cle.split_once(':').ok_or("une clé de session doit être de la forme id:clé")?;
+            if id.is_empty() || id.contains('.') {
+                return Err(format!("id de clé de session invalide: {id}").into());
+            }
+            let cle = base64::engine::general_purpose::STANDARD.decode(cle)?;
+            let cle = Aes256Gcm::new_from_slice(&cle).map_err(|_| format!("la clé de session {id} doit faire 32 octets"))?;
+            Ok((id.to_owned(), cle))
+        })
+        .collect()
+}
+
+pub fn sans_etat() -> bool {
+    !CLES.read().expect("Failed due to poisoned lock").is_empty()
+}
+
+pub fn charger(sessions: &RwLock<HashMap<SessionId, Session>>, cookie: &str) -> Option<Session> {
+    if sans_etat() {
+        desceller(&CLES.read().expect("Failed due to poisoned lock"), cookie)
+    } else {
+        sessions
+            .read()
+            .expect("Failed due to poisoned lock")
+            .get(&SessionId::from(cookie.to_owned()))
+            .cloned()
+    }
+}
+
+// Retourne la valeur du cookie Session-Id
+pub fn enregistrer(sessions: &RwLock<HashMap<SessionId, Session>>, id: SessionId, session: Session) -> Result<String, Erreur> {
+    if sans_etat() {
+        verifier_taille(sceller(&CLES.read().expect("Failed due to poisoned lock"), &session))
+    } else {
+        let valeur = id.as_ref().to_owned();
+        sessions.write().expect("Failed due to poisoned lock").insert(id, session);
+        Ok(valeur)
+    }
+}
+
+// Un cookie trop grand serait ignoré sans erreur par le navigateur
+fn verifier_taille(valeur: String) -> Result<String, Erreur> {
+    let taille = cookies::nom_session().len() + 1 + valeur.len();
+    if taille > TAILLE_MAX_COOKIE {
+        return Err(Erreur::SessionTropVolumineuse(taille));
+    }
+    Ok(valeur)
+}
+
+// Une session sans état est invalidée en remplaçant le cookie
+pub fn retirer(sessions: &RwLock<HashMap<SessionId, Session>>, cookie: &str) -> Option<Session> {
+    if sans_etat() {
+        desceller(&CLES.read().expect("Failed due to poisoned lock"), cookie)
+    } else {
+        sessions
+            .write()
+            .expect("Failed due to poisoned lock")
+            .remove(&SessionId::from(cookie.to_owned()))
+    }
+}
+
+// Retourne le nombre de sessions retirées; sans état, la déconnexion est retenue et vérifiée au déchiffrement
+pub fn deconnecter(sessions: &RwLock<HashMap<SessionId, Session>>, deconnexion: &Identite) -> usize {
+    if sans_etat() {
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        retenir(&mut REVOCATIONS.write().expect("Failed due to poisoned lock"), deconnexion, maintenant);
+        return 0;
+    }
+
+    let mut sessions = sessions.write().expect("Failed due to poisoned lock");
+    let avant = sessions.len();
+    sessions.retain(|_, session| match session {
+        Session::Authenticated(_, token) => !token.identite().visee_par(deconnexion),
+        _ => true,
+    });
+    avant - sessions.len()
+}
+
+fn retenir(revocations: &mut HashMap<Identite, u64>, deconnexion: &Identite, maintenant: u64) {
+    revocations.retain(|_, instant| *instant + RETENTION_REVOCATIONS >= maintenant);
+    if revocations.len() >= MAX_REVOCATIONS && !revocations.contains_key(deconnexion) {
+        if let Some(ancienne) = revocations
+            .iter()
+            .min_by_key(|(_, instant)| **instant)
+            .map(|(identite, _)| identite.clone())
+        {
+            revocations.remove(&ancienne);
+        }
+    }
+    revocations.insert(deconnexion.clone(), maintenant);
+}
+
+fn revoquee(identite: &Identite, authentification: u64) -> bool {
+    REVOCATIONS
+        .read()
+        .expect("Failed due to poisoned lock")
+        .iter()
+        .any(|(deconnexion, &instant)| identite.visee_par(deconnexion) && instant >= authentification)
+}
+
+// id.nonce||chiffré
+fn sceller(cles: &[(String, Aes256Gcm)], session: &Session) -> String {
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let scellee = match session {
+        Session::AuthenticationRequested(f, c, csrf, d) => Scellee::Demandee {
+            fournisseur: f.to_string(),
+            csrf: csrf.secret().to_owned(),
+            redirection: c.redirect_url().map(|url| url.to_string()).unwrap_or_default(),
+            demande: d.clone(),
+            emission: maintenant.as_secs(),
+        },
+        Session::Authenticated(f, token) => Scellee::Authentifiee {
+            fournisseur: f.to_string(),
+            jeton: token.secret().to_owned(),
+            expiration: (maintenant + token.expire_dans()).as_secs(),
+            demande: token.demande().clone(),
+            identite: token.identite().clone(),
+            authentification: maintenant.as_secs(),
+            dpop: token.dpop().cloned(),
+        },
+    };
+    chiffrer(cles, &scellee)
+}
+
+fn chiffrer(cles: &[(String, Aes256Gcm)], scellee: &Scellee) -> String {
+    let clair = serde_json::to_vec(scellee).unwrap_or_default();
+
+    let (id, cle) = &cles[0];
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let chiffre = cle.encrypt(&nonce, Payload { msg: &clair, aad: AAD }).expect("chiffrement AES-GCM");
+
+    let mut scelle = nonce.to_vec();
+    scelle.extend(chiffre);
+    format!("{id}.{}", URL_SAFE_NO_PAD.encode(scelle))
+}
+
+// Un cookie altéré, chiffré avec une clé retirée ou expiré est traité comme une session inexistante
+fn desceller(cles: &[(String, Aes256Gcm)], cookie: &str) -> Option<Session> {
+    let (id, scelle) = cookie.split_once('.')?;
+    let scelle = URL_SAFE_NO_PAD.decode(scelle).ok()?;
+    if scelle.len() < TAILLE_NONCE {
+        return None;
+    }
+    let (nonce, chiffre) = scelle.split_at(TAILLE_NONCE);
+
+    let (_, cle) = cles.iter().find(|(i, _)| i == id)?;
+    let clair = cle.decrypt(Nonce::from_slice(nonce), Payload { msg: chiffre, aad: AAD }).ok()?;
+
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    match serde_json::from_slice(&clair).ok()? {
+        Scellee::Demandee {
+            fournisseur,
+            csrf,
+            redirection,
+            demande,
+            emission,
+        } => {
+            if emission + cookies::DUREE_AUTORISATION.as_secs() < maintenant {
+                return None;
+            }
+            let f = Fournisseur::from(fournisseur.as_str());
+            let client = client::client_oauth(&f).set_redirect_uri(RedirectUrl::new(redirection).ok()?);
+            Some(Session::new(f, client, CsrfToken::new(csrf), demande))
+        }
+        Scellee::Authentifiee {
+            fournisseur,
+            jeton,
+            expiration,
+            demande,
+            identite,
+            authentification,
+            dpop,
+        } => {
+            if revoquee(&identite, authentification) {
+                return None;
+            }
+            let token = Token::new(
+                AccessToken::new(jeton),
+                Duration::from_secs(expiration.saturating_sub(maintenant)),
+                demande,
+            )
+            .identifier(identite)
+            .lier(dpop);
+            Some(Session::Authenticated(fournisseur.as_str().into(), token))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+
+    fn cle(id: &str, octet: u8) -> String {
+        format!("{id}:{}", STANDARD.encode([octet; 32]))
+    }
+
+    #[test]
+    fn rotation() {
+        let v1 = analyser_cles(&[cle("v1", 1)]).unwrap();
+        let demande = Demande {
+            scopes: vec!["openid".to_owned()],
+            parametres: Vec::new(),
+        };
+        let token = Token::new(AccessToken::new("LOL".to_owned()), Duration::from_secs(3600), demande);
+        let cookie = sceller(&v1, &Session::Authenticated(Fournisseur::Google, token));
+        assert!(cookie.starts_with("v1."));
+
+        // v2 chiffre, v1 déchiffre encore
+        let v2_v1 = analyser_cles(&[cle("v2", 2), cle("v1", 1)]).unwrap();
+        match desceller(&v2_v1, &cookie) {
+            Some(Session::Authenticated(f, token)) => {
+                assert_eq!(f.to_string(), "Google");
+                assert_eq!(token.secret(), "LOL");
+            }
+            _ => panic!("session non descellée"),
+        }
+
+        let mut altere = cookie.clone();
+        let dernier = altere.pop().unwrap();
+        altere.push(if dernier == 'A' { 'B' } else { 'A' });
+        assert!(desceller(&v2_v1, &altere).is_none());
+
+        let v2 = analyser_cles(&[cle("v2", 2)]).unwrap();
+        assert!(desceller(&v2, &cookie).is_none());
+    }
+
+    #[test]
+    fn demande_expiree() {
+        let v1 = analyser_cles(&[cle("v1", 1)]).unwrap();
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let demandee = |emission: u64| Scellee::Demandee {
+            fournisseur: "Google".to_owned(),
+            csrf: "LOL".to_owned(),
+            redirection: "http://localhost/auth".to_owned(),
+            demande: Demande {
+                scopes: Vec::new(),
+                parametres: Vec::new(),
+            },
+            emission,
+        };
+        assert!(matches!(
+            desceller(&v1, &chiffrer(&v1, &demandee(maintenant))),
+            Some(Session::AuthenticationRequested(..))
+        ));
+        let ancienne = maintenant - cookies::DUREE_AUTORISATION.as_secs() - 1;
+        assert!(desceller(&v1, &chiffrer(&v1, &demandee(ancienne))).is_none());
+    }
+
+    #[test]
+    fn taille_cookie() {
+        assert!(verifier_taille("LOL".repeat(1000)).is_ok());
+        assert_eq!(verifier_taille("LOL".repeat(1400)).unwrap_err().code(), "session_trop_volumineuse");
+    }
+
+    #[test]
+    fn revocations_dedoublonnees() {
+        let identite = |sid: &str| Identite {
+            iss: Some("https://op.exemple.com".to_owned()),
+            sub: None,
+            sid: Some(sid.to_owned()),
+        };
+        let mut revocations = HashMap::new();
+        retenir(&mut revocations, &identite("LOL"), 100);
+        retenir(&mut revocations, &identite("LOL"), 200);
+        assert_eq!(revocations.len(), 1);
+        assert_eq!(revocations[&identite("LOL")], 200);
+
+        for i in 0..MAX_REVOCATIONS {
+            retenir(&mut revocations, &identite(&i.to_string()), 300 + i as u64);
+        }
+        assert_eq!(revocations.len(), MAX_REVOCATIONS);
+        // La plus ancienne est oubliée
+        assert!(!revocations.contains_key(&identite("LOL")));
+    }
+}
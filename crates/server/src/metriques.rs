@@ -0,0 +1,66 @@
+use crate::session::{Fournisseur, Session, SessionId};
+use lazy_static::lazy_static;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref REGISTRE: Registry = Registry::new_custom(Some("test_oidc".to_owned()), None).expect("préfixe valide");
+    pub static ref CONNEXIONS_DEMARREES: IntCounterVec = compteur("connexions_demarrees_total", "Redirections vers l'OP");
+    pub static ref CONNEXIONS_COMPLETEES: IntCounterVec = compteur("connexions_completees_total", "Sessions authentifiées");
+    pub static ref ECHECS_ECHANGE_JETON: IntCounterVec = compteur("echecs_echange_jeton_total", "Échecs au token endpoint");
+    pub static ref DECONNEXIONS_BACKCHANNEL: IntCounterVec = compteur("deconnexions_backchannel_total", "Logout tokens valides reçus de l'OP");
+    pub static ref REJETS_CSRF: IntCounter = {
+        let rejets = IntCounter::new("rejets_csrf_total", "Requêtes rejetées par la validation Csrf").expect("métrique valide");
+        REGISTRE.register(Box::new(rejets.clone())).expect("métrique unique");
+        rejets
+    };
+    pub static ref LATENCE_USERINFO: HistogramVec = {
+        let opts = HistogramOpts::new("latence_userinfo_secondes", "Durée des appels au userinfo endpoint");
+        let latence = HistogramVec::new(opts, &["fournisseur"]).expect("métrique valide");
+        REGISTRE.register(Box::new(latence.clone())).expect("métrique unique");
+        latence
+    };
+    static ref SESSIONS: IntGaugeVec = {
+        let sessions = IntGaugeVec::new(Opts::new("sessions", "Sessions en mémoire par état"), &["etat"]).expect("métrique valide");
+        REGISTRE.register(Box::new(sessions.clone())).expect("métrique unique");
+        sessions
+    };
+}
+
+fn compteur(nom: &str, aide: &str) -> IntCounterVec {
+    let compteur = IntCounterVec::new(Opts::new(nom, aide), &["fournisseur"]).expect("métrique valide");
+    REGISTRE.register(Box::new(compteur.clone())).expect("métrique unique");
+    compteur
+}
+
+// Format texte Prometheus. Le nombre de sessions est calculé au moment de la collecte.
+pub fn exporter(sessions: &HashMap<SessionId, Session>) -> String {
+    let (demandees, authentifiees) = sessions.values().fold((0, 0), |(d, a), session| match session {
+        Session::AuthenticationRequested(..) => (d + 1, a),
+        Session::Authenticated(..) => (d, a + 1),
+    });
+    SESSIONS.with_label_values(&["AuthenticationRequested"]).set(demandees);
+    SESSIONS.with_label_values(&["Authenticated"]).set(authentifiees);
+
+    // Une série étiquetée n'est exportée qu'une fois créée: elles le sont à zéro pour chaque fournisseur.
+    // Le compteur sans étiquette est exporté dès son enregistrement.
+    for f in Fournisseur::TOUS {
+        let fournisseur = f.to_string();
+        for compteur in [
+            &*CONNEXIONS_DEMARREES,
+            &*CONNEXIONS_COMPLETEES,
+            &*ECHECS_ECHANGE_JETON,
+            &*DECONNEXIONS_BACKCHANNEL,
+        ] {
+            compteur.with_label_values(&[&fournisseur]);
+        }
+        LATENCE_USERINFO.with_label_values(&[&fournisseur]);
+    }
+    lazy_static::initialize(&REJETS_CSRF);
+
+    let mut tampon = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRE.gather(), &mut tampon) {
+        tracing::warn!("{e}");
+    }
+    String::from_utf8(tampon).unwrap_or_default()
+}
@@ -0,0 +1,168 @@
+use crate::client;
+use crate::config::{self, MethodeAuth, ModePar};
+use crate::decouverte;
+use crate::erreur::Erreur;
+use crate::session::Fournisseur;
+use oauth2::url::Url;
+use serde_json::Value;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Pushed Authorization Requests (RFC 9126)
+const DELAI: Duration = Duration::from_secs(5);
+
+// Authentification du client au PAR endpoint, la même qu'au token endpoint (2.1)
+enum AuthClient {
+    Basic(String, String),
+    Formulaire(Vec<(&'static str, String)>),
+}
+
+// URL de redirection vers l'OP: les paramètres de la requête d'autorisation sont poussés au PAR endpoint
+// et seuls client_id et request_uri sont transmis par le navigateur. Sans PAR, l'URL est retournée telle quelle.
+pub async fn redirection(f: &Fournisseur, authorize_url: Url) -> Result<Url, Erreur> {
+    let endpoint = match config::fournisseur(&f.to_string()).par {
+        ModePar::Jamais => None,
+        ModePar::Auto => endpoint(f).await.unwrap_or_else(|e| {
+            warn!("discovery: {e}");
+            None
+        }),
+        ModePar::Toujours => Some(
+            endpoint(f)
+                .await
+                .map_err(Erreur::FournisseurInjoignable)?
+                .ok_or_else(|| Erreur::FournisseurInjoignable("pushed_authorization_request_endpoint absent du document discovery".to_owned()))?,
+        ),
+    };
+
+    match endpoint {
+        Some(endpoint) => pousser(&endpoint, authorize_url, auth_client(f)?).await,
+        None => Ok(authorize_url),
+    }
+}
+
+fn auth_client(f: &Fournisseur) -> Result<AuthClient, Erreur> {
+    let (id, secret) = f.secrets();
+    Ok(match config::fournisseur(&f.to_string()).methode_auth {
+        MethodeAuth::ClientSecretPost => AuthClient::Formulaire(vec![("client_secret", secret.to_owned())]),
+        MethodeAuth::ClientSecretBasic => AuthClient::Basic(id.to_owned(), secret.to_owned()),
+        MethodeAuth::ClientSecretJwt | MethodeAuth::PrivateKeyJwt => {
+            AuthClient::Formulaire(client::parametres_assertion(f).map_err(|e| Erreur::AssertionClient(e.to_string()))?)
+        }
+    })
+}
+
+async fn endpoint(f: &Fournisseur) -> Result<Option<String>, String> {
+    Ok(decouverte::champ(
+        &decouverte::document(f).await?,
+        "pushed_authorization_request_endpoint",
+    ))
+}
+
+// 2.1 et 2.2: la requête est postée en formulaire, l'OP répond 201 avec request_uri et expires_in
+async fn pousser(endpoint: &str, authorize_url: Url, auth: AuthClient) -> Result<Url, Erreur> {
+    let mut formulaire = authorize_url.query_pairs().into_owned().collect::<Vec<_>>();
+    let client_id = formulaire
+        .iter()
+        .find(|(k, _)| k == "client_id")
+        .map(|(_, v)| v.clone())
+        .unwrap_or_default();
+
+    let client = reqwest::Client::builder().timeout(DELAI).build().unwrap_or_default();
+    let requete = match auth {
+        AuthClient::Basic(id, secret) => client.post(endpoint).basic_auth(id, Some(secret)),
+        AuthClient::Formulaire(parametres) => {
+            formulaire.extend(parametres.into_iter().map(|(k, v)| (k.to_owned(), v)));
+            client.post(endpoint)
+        }
+    };
+    let response = requete
+        .form(&formulaire)
+        .send()
+        .await
+        .map_err(|e| Erreur::FournisseurInjoignable(e.to_string()))?;
+
+    let statut = response.status();
+    let corps = response.json::<Value>().await.unwrap_or_default();
+    if !statut.is_success() {
+        return Err(match corps.get("error").and_then(Value::as_str) {
+            Some(error) => Erreur::Fournisseur {
+                error: error.to_owned(),
+                description: corps.get("error_description").and_then(Value::as_str).map(str::to_owned),
+            },
+            None => Erreur::FournisseurInjoignable(format!("{endpoint}: statut {}", statut.as_u16())),
+        });
+    }
+    let request_uri = corps
+        .get("request_uri")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Erreur::FournisseurInjoignable(format!("{endpoint}: request_uri absent")))?;
+    let expires_in = corps.get("expires_in").and_then(Value::as_u64);
+    info!(expires_in, "requête d'autorisation poussée");
+
+    let mut url = authorize_url;
+    url.set_query(None);
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("request_uri", request_uri);
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sante;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    // OP simulé: discovery et PAR endpoint; client_secret LOL requis
+    fn op() -> SocketAddr {
+        let (adresse, serveur) = warp::serve(
+            warp::path!(".well-known" / "openid-configuration")
+                .and(warp::host::optional())
+                .map(|hote: Option<warp::host::Authority>| {
+                    let hote = hote.map(|h| h.to_string()).unwrap_or_default();
+                    warp::reply::json(&json!({ "pushed_authorization_request_endpoint": format!("http://{hote}/par") }))
+                })
+                .or(warp::post()
+                    .and(warp::path("par"))
+                    .and(warp::body::form())
+                    .map(|formulaire: HashMap<String, String>| {
+                        if formulaire.get("client_secret").map(String::as_str) != Some("LOL") {
+                            return warp::reply::with_status(warp::reply::json(&json!({ "error": "invalid_client" })), StatusCode::UNAUTHORIZED);
+                        }
+                        let reponse = json!({
+                            "request_uri": format!("urn:ietf:params:oauth:request_uri:{}", formulaire["state"]),
+                            "expires_in": 60,
+                        });
+                        warp::reply::with_status(warp::reply::json(&reponse), StatusCode::CREATED)
+                    })),
+        )
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(serveur);
+        adresse
+    }
+
+    #[tokio::test]
+    async fn requete_poussee() {
+        let op = op();
+        let document = sante::obtenir(&reqwest::Client::new(), &format!("http://{op}/.well-known/openid-configuration"))
+            .await
+            .unwrap();
+        let endpoint = decouverte::champ(&document, "pushed_authorization_request_endpoint").unwrap();
+        assert_eq!(endpoint, format!("http://{op}/par"));
+
+        let authorize_url = Url::parse("https://op.exemple.com/authorize?response_type=code&client_id=client&state=bWJq&scope=openid").unwrap();
+        let secret = AuthClient::Formulaire(vec![("client_secret", "LOL".to_owned())]);
+        let url = pousser(&endpoint, authorize_url.clone(), secret).await.unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://op.exemple.com/authorize?client_id=client&request_uri=urn%3Aietf%3Aparams%3Aoauth%3Arequest_uri%3AbWJq"
+        );
+
+        let refus = pousser(&endpoint, authorize_url, AuthClient::Formulaire(Vec::new())).await.unwrap_err();
+        assert_eq!(refus.code(), "erreur_fournisseur");
+    }
+}
@@ -0,0 +1,135 @@
+use crate::dpop::CleDpop;
+use crate::session::{Demande, Identite, Session, SessionId, Token};
+use oauth2::AccessToken;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Seules les sessions authentifiées et non expirées sont conservées. Une session en attente
+// du retour de l'OP est perdue: l'utilisateur devra se reconnecter.
+#[derive(Serialize, Deserialize)]
+struct SessionPersistee {
+    id: String,
+    fournisseur: String,
+    jeton: String,
+    // Instant unix d'expiration: le temps d'arrêt du serveur est décompté à la restauration.
+    // Absent des fichiers d'une version antérieure: la session est alors écartée.
+    #[serde(default)]
+    expiration: u64,
+    demande: Demande,
+    #[serde(default)]
+    identite: Identite,
+    #[serde(default)]
+    dpop: Option<CleDpop>,
+}
+
+// Le fichier contient des jetons d'accès: il n'est lisible que par le propriétaire
+pub fn sauvegarder(sessions: &HashMap<SessionId, Session>, chemin: &Path) -> Result<usize, Box<dyn Error>> {
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let persistees = sessions
+        .iter()
+        .filter_map(|(id, session)| match session {
+            Session::Authenticated(f, token) if !token.is_expired() => Some(SessionPersistee {
+                id: id.as_ref().to_owned(),
+                fournisseur: f.to_string(),
+                jeton: token.secret().to_owned(),
+                expiration: (maintenant + token.expire_dans()).as_secs(),
+                demande: token.demande().clone(),
+                identite: token.identite().clone(),
+                dpop: token.dpop().cloned(),
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Écrit dans un fichier temporaire créé 0600 puis renommé: le mode s'applique aussi quand le fichier existait
+    let mut temporaire = chemin.as_os_str().to_owned();
+    temporaire.push(".tmp");
+    let temporaire = Path::new(&temporaire);
+    let _ = std::fs::remove_file(temporaire);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut fichier = options.open(temporaire).map_err(|e| format!("{}: {e}", temporaire.to_string_lossy()))?;
+    fichier.write_all(serde_json::to_string(&persistees)?.as_bytes())?;
+    fichier.sync_all()?;
+    std::fs::rename(temporaire, chemin).map_err(|e| format!("{}: {e}", chemin.to_string_lossy()))?;
+
+    Ok(persistees.len())
+}
+
+// Un fichier absent n'est pas une erreur (premier démarrage)
+pub fn restaurer(sessions: &mut HashMap<SessionId, Session>, chemin: &Path) -> Result<usize, Box<dyn Error>> {
+    let contenu = match std::fs::read(chemin) {
+        Ok(contenu) => contenu,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("{}: {e}", chemin.to_string_lossy()).into()),
+    };
+    let persistees: Vec<SessionPersistee> = serde_json::from_slice(&contenu)?;
+
+    let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut nombre = 0;
+    for p in persistees.into_iter().filter(|p| p.expiration > maintenant) {
+        nombre += 1;
+        let token = Token::new(AccessToken::new(p.jeton), Duration::from_secs(p.expiration - maintenant), p.demande)
+            .identifier(p.identite)
+            .lier(p.dpop);
+        sessions.insert(p.id.into(), Session::Authenticated(p.fournisseur.as_str().into(), token));
+    }
+
+    Ok(nombre)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aller_retour() {
+        let demande = Demande {
+            scopes: vec!["openid".to_owned()],
+            parametres: vec![("prompt".to_owned(), "consent".to_owned())],
+        };
+        let token = Token::new(AccessToken::new("LOL".to_owned()), Duration::from_secs(3600), demande.clone());
+        let mut sessions = HashMap::new();
+        sessions.insert(SessionId::from("BOUH".to_owned()), Session::Authenticated("Google".into(), token));
+
+        let chemin = std::env::temp_dir().join("test-oidc-sessions.json");
+        std::fs::write(&chemin, "[]").unwrap();
+        assert_eq!(sauvegarder(&sessions, &chemin).unwrap(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&chemin).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let mut restaurees = HashMap::new();
+        assert_eq!(restaurer(&mut restaurees, &chemin).unwrap(), 1);
+        match restaurees.get(&SessionId::from("BOUH".to_owned())) {
+            Some(Session::Authenticated(f, token)) => {
+                assert_eq!(f.to_string(), "Google");
+                assert_eq!(token.secret(), "LOL");
+                assert!(token.demande() == &demande);
+            }
+            _ => panic!("session non restaurée"),
+        }
+    }
+
+    #[test]
+    fn expiree_pendant_l_arret() {
+        let chemin = std::env::temp_dir().join("test-oidc-sessions-expirees.json");
+        let persistees = r#"[
+            {"id": "LOL", "fournisseur": "Google", "jeton": "LOL", "expiration": 1, "demande": {"scopes": [], "parametres": []}},
+            {"id": "BOUH", "fournisseur": "Google", "jeton": "BOUH", "expire_dans": 3600, "demande": {"scopes": [], "parametres": []}}
+        ]"#;
+        std::fs::write(&chemin, persistees).unwrap();
+
+        let mut restaurees = HashMap::new();
+        assert_eq!(restaurer(&mut restaurees, &chemin).unwrap(), 0);
+        assert!(restaurees.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config;
+use crate::session::Fournisseur;
+use lazy_static::lazy_static;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio_rustls::rustls::crypto::ring::sign::any_supported_type;
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::SignatureScheme;
+use webpki::EndEntityCert;
+
+// Les endpoints des fournisseurs ne sont pas sollicités à chaque sonde
+const DUREE_CACHE: Duration = Duration::from_secs(60);
+const DELAI: Duration = Duration::from_secs(5);
+
+// Message signé par la clé privée pour la comparer au certificat
+const CONTROLE: &[u8] = b"test-oidc";
+// Schémas proposés à la clé
+const SCHEMAS: [SignatureScheme; 4] = [
+    SignatureScheme::ECDSA_NISTP256_SHA256,
+    SignatureScheme::ECDSA_NISTP384_SHA384,
+    SignatureScheme::ED25519,
+    SignatureScheme::RSA_PKCS1_SHA256,
+];
+
+lazy_static! {
+    static ref TLS: RwLock<Option<Result<(), String>>> = RwLock::new(None);
+    static ref VERIFICATIONS: RwLock<HashMap<String, (Instant, Verification)>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Clone)]
+struct Verification {
+    pret: bool,
+    detail: Map<String, Value>,
+}
+
+// Certificat et clé privée analysés comme par le listener TLS; la clé doit correspondre au certificat
+pub fn charger_tls(cert: &Path, cle: &Path) -> Result<CertifiedKey, Box<dyn Error>> {
+    let resultat = cle_certifiee(cert, cle);
+    *TLS.write().expect("Failed due to poisoned lock") = Some(resultat.as_ref().map(|_| ()).map_err(Clone::clone));
+    resultat.map_err(Into::into)
+}
+
+fn cle_certifiee(cert: &Path, cle: &Path) -> Result<CertifiedKey, String> {
+    let nom = |chemin: &Path| chemin.to_string_lossy().into_owned();
+    let ouvrir = |chemin: &Path| File::open(chemin).map(BufReader::new).map_err(|e| format!("{}: {e}", nom(chemin)));
+
+    let certs = rustls_pemfile::certs(&mut ouvrir(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {e}", nom(cert)))?;
+    let certificat = certs.first().ok_or(format!("{}: aucun certificat", nom(cert)))?;
+    let certificat = EndEntityCert::try_from(certificat).map_err(|e| format!("{}: certificat invalide: {e}", nom(cert)))?;
+    let cle_privee = rustls_pemfile::private_key(&mut ouvrir(cle)?)
+        .map_err(|e| format!("{}: {e}", nom(cle)))?
+        .ok_or(format!("{}: clé privée absente", nom(cle)))?;
+    let cle_privee = any_supported_type(&cle_privee).map_err(|e| format!("{}: {e}", nom(cle)))?;
+
+    // Une signature de la clé privée doit être vérifiée par la clé publique du certificat
+    let signataire = cle_privee
+        .choose_scheme(&SCHEMAS)
+        .ok_or(format!("{}: type de clé non supporté", nom(cle)))?;
+    let signature = signataire.sign(CONTROLE).map_err(|e| format!("{}: {e}", nom(cle)))?;
+    let verification = match signataire.scheme() {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => webpki::ring::ECDSA_P256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => webpki::ring::ECDSA_P384_SHA384,
+        SignatureScheme::ED25519 => webpki::ring::ED25519,
+        _ => webpki::ring::RSA_PKCS1_2048_8192_SHA256,
+    };
+    certificat
+        .verify_signature(verification, CONTROLE, &signature)
+        .map_err(|_| format!("{}: la clé privée ne correspond pas au certificat {}", nom(cle), nom(cert)))?;
+
+    Ok(CertifiedKey::new(certs, cle_privee))
+}
+
+// Détail par composant; le serveur est prêt si tous les composants le sont
+pub async fn pret() -> (bool, Value) {
+    let (tls_pret, tls) = match &*TLS.read().expect("Failed due to poisoned lock") {
+        None => (true, json!("désactivé")),
+        Some(Ok(())) => (true, json!("ok")),
+        Some(Err(e)) => (false, json!({ "erreur": e })),
+    };
+
+    let inconnus = config::noms()
+        .into_iter()
+        .filter(|nom| !Fournisseur::TOUS.iter().any(|f| &f.to_string() == nom))
+        .collect::<Vec<_>>();
+    let registre = if inconnus.is_empty() {
+        json!("ok")
+    } else {
+        json!({ "erreur": format!("fournisseurs inconnus: {}", inconnus.join(", ")) })
+    };
+
+    let mut pret = tls_pret && inconnus.is_empty();
+    let mut fournisseurs = Map::new();
+    for f in Fournisseur::TOUS {
+        let verification = verifier(&f).await;
+        pret &= verification.pret;
+        fournisseurs.insert(f.to_string(), Value::Object(verification.detail));
+    }
+
+    let statut = if pret { "prêt" } else { "non prêt" };
+    (
+        pret,
+        json!({ "statut": statut, "tls": tls, "registre": registre, "fournisseurs": fournisseurs }),
+    )
+}
+
+// Configuration du client, puis discovery et JWKS de l'OP
+async fn verifier(f: &Fournisseur) -> Verification {
+    let nom = f.to_string();
+    if let Some((instant, verification)) = VERIFICATIONS.read().expect("Failed due to poisoned lock").get(&nom) {
+        if instant.elapsed() < DUREE_CACHE {
+            let mut verification = verification.clone();
+            verification.detail.insert("ageSecondes".into(), Value::from(instant.elapsed().as_secs()));
+            return verification;
+        }
+    }
+
+    let mut detail = Map::new();
+    let client_id = !f.secrets().0.trim().is_empty();
+    detail.insert("clientId".into(), json!(if client_id { "ok" } else { "manquant" }));
+    detail.insert("methodeAuth".into(), json!(config::fournisseur(&nom).methode_auth.to_string()));
+
+    let debut = Instant::now();
+    let client = reqwest::Client::builder().timeout(DELAI).build().unwrap_or_default();
+    let jwks = match obtenir(&client, f.discovery()).await {
+        Ok(document) => {
+            detail.insert("discovery".into(), json!("ok"));
+            match document.get("jwks_uri").and_then(Value::as_str) {
+                Some(uri) => obtenir(&client, uri).await.map(|_| ()),
+                None => Err("jwks_uri absent du document discovery".to_owned()),
+            }
+        }
+        Err(e) => {
+            detail.insert("discovery".into(), json!({ "erreur": e }));
+            Err("discovery en erreur".to_owned())
+        }
+    };
+    let jwks_pret = jwks.is_ok();
+    detail.insert(
+        "jwks".into(),
+        match jwks {
+            Ok(()) => json!("ok"),
+            Err(e) => json!({ "erreur": e }),
+        },
+    );
+    detail.insert("dureeMs".into(), Value::from(debut.elapsed().as_millis() as u64));
+
+    let verification = Verification {
+        pret: client_id && jwks_pret,
+        detail,
+    };
+    VERIFICATIONS
+        .write()
+        .expect("Failed due to poisoned lock")
+        .insert(nom, (Instant::now(), verification.clone()));
+
+    verification
+}
+
+pub async fn obtenir(client: &reqwest::Client, url: &str) -> Result<Value, String> {
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{url}: statut {}", response.status().as_u16()));
+    }
+    response.json::<Value>().await.map_err(|e| format!("{url}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{generate_simple_self_signed, CertifiedKey as Genere, KeyPair};
+
+    fn ecrire(nom: &str, pem: &str) -> std::path::PathBuf {
+        let chemin = std::env::temp_dir().join(nom);
+        std::fs::write(&chemin, pem).unwrap();
+        chemin
+    }
+
+    #[test]
+    fn certificat_et_cle() {
+        let Genere { cert, key_pair } = generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let chemin_cert = ecrire("test-oidc-sante-cert.pem", &cert.pem());
+        let chemin_cle = ecrire("test-oidc-sante-cle.pem", &key_pair.serialize_pem());
+        assert!(cle_certifiee(&chemin_cert, &chemin_cle).is_ok());
+
+        // Clé d'une autre paire
+        let autre = ecrire("test-oidc-sante-autre-cle.pem", &KeyPair::generate().unwrap().serialize_pem());
+        let erreur = cle_certifiee(&chemin_cert, &autre).unwrap_err();
+        assert!(erreur.contains("ne correspond pas"), "{erreur}");
+
+        // Certificat au format PEM mais au contenu corrompu
+        let corrompu = ecrire(
+            "test-oidc-sante-corrompu.pem",
+            "-----BEGIN CERTIFICATE-----\nTE9MIEJPVUg=\n-----END CERTIFICATE-----\n",
+        );
+        let erreur = cle_certifiee(&corrompu, &chemin_cle).unwrap_err();
+        assert!(erreur.contains("certificat invalide"), "{erreur}");
+
+        // Clé privée absente du fichier
+        assert!(cle_certifiee(&chemin_cert, &chemin_cert).unwrap_err().contains("clé privée absente"));
+    }
+}
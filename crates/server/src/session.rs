@@ -1,7 +1,11 @@
-use oauth2::basic::BasicClient;
+use crate::client::ClientOidc;
+use crate::dpop::CleDpop;
+use commun::autorisation::PARAMETRES_AUTORISATION;
 use oauth2::{AccessToken, CsrfToken};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::time::{Duration, Instant};
 
@@ -15,6 +19,9 @@ const TOKEN_MS: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/
 const TOKEN_GG: &str = "https://oauth2.googleapis.com/token";
 const INFOS_MS: &str = "https://graph.microsoft.com/oidc/userinfo";
 const INFOS_GG: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+const DISCOVERY_MS: &str = "https://login.microsoftonline.com/consumers/v2.0/.well-known/openid-configuration";
+const DISCOVERY_GG: &str = "https://accounts.google.com/.well-known/openid-configuration";
+const SCOPES_DEFAUT: &str = "openid email profile";
 
 pub fn random_token(len: usize) -> String {
     rand::thread_rng()
@@ -45,15 +52,58 @@ impl AsRef<str> for SessionId {
     }
 }
 
+// Scopes et paramètres additionnels de la requête d'autorisation
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Demande {
+    pub scopes: Vec<String>,
+    pub parametres: Vec<(String, String)>,
+}
+
+impl Demande {
+    fn response_mode(&self) -> Option<&str> {
+        self.parametres.iter().find(|(k, _)| k == "response_mode").map(|(_, v)| v.as_str())
+    }
+
+    // La réponse revient par un POST cross-site de l'OP
+    pub fn form_post(&self) -> bool {
+        matches!(self.response_mode(), Some("form_post" | "form_post.jwt"))
+    }
+
+    // JWT Secured Authorization Response Mode (JARM): jwt, query.jwt, fragment.jwt ou form_post.jwt
+    pub fn jarm(&self) -> bool {
+        self.response_mode().is_some_and(|mode| mode == "jwt" || mode.ends_with(".jwt"))
+    }
+}
+
+impl From<&HashMap<String, String>> for Demande {
+    fn from(body: &HashMap<String, String>) -> Self {
+        let scopes = match body.get("scopes") {
+            Some(scopes) if !scopes.trim().is_empty() => scopes,
+            _ => SCOPES_DEFAUT,
+        };
+        let scopes = scopes.split_whitespace().map(str::to_owned).collect();
+
+        let parametres = PARAMETRES_AUTORISATION
+            .iter()
+            .filter_map(|&p| match body.get(p) {
+                Some(v) if !v.is_empty() => Some((p.to_owned(), v.to_owned())),
+                _ => None,
+            })
+            .collect();
+
+        Demande { scopes, parametres }
+    }
+}
+
 #[derive(Clone)]
 pub enum Session {
-    AuthenticationRequested(Fournisseur, Box<BasicClient>, CsrfToken),
+    AuthenticationRequested(Fournisseur, Box<ClientOidc>, CsrfToken, Demande),
     Authenticated(Fournisseur, Token),
 }
 
 impl Session {
-    pub fn new(f: Fournisseur, c: BasicClient, csrf: CsrfToken) -> Self {
-        Session::AuthenticationRequested(f, Box::new(c), csrf)
+    pub fn new(f: Fournisseur, c: ClientOidc, csrf: CsrfToken, d: Demande) -> Self {
+        Session::AuthenticationRequested(f, Box::new(c), csrf, d)
     }
 
     pub fn authentication_completed(self, t: Token) -> Self {
@@ -72,26 +122,86 @@ impl Session {
     }
 }
 
+// iss, sub et sid de l'id_token, pour retrouver les sessions visées par une déconnexion de l'OP
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Identite {
+    pub iss: Option<String>,
+    pub sub: Option<String>,
+    pub sid: Option<String>,
+}
+
+impl Identite {
+    // Avec un sid, seule la session correspondante est visée; sinon toutes les sessions du sub.
+    // Le sid n'est unique que pour un même OP.
+    pub fn visee_par(&self, deconnexion: &Identite) -> bool {
+        if deconnexion.iss.is_some() && self.iss != deconnexion.iss {
+            return false;
+        }
+        match (&deconnexion.sid, &deconnexion.sub) {
+            (Some(sid), _) => self.sid.as_ref() == Some(sid),
+            (None, Some(sub)) => self.sub.as_ref() == Some(sub),
+            (None, None) => false,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Token {
     token: AccessToken,
     creation: Instant,
     expired_in: Duration,
+    demande: Demande,
+    identite: Identite,
+    dpop: Option<CleDpop>,
 }
 
 impl Token {
-    pub fn new(token: AccessToken, expired_in: Duration) -> Self {
+    pub fn new(token: AccessToken, expired_in: Duration, demande: Demande) -> Self {
         let creation = Instant::now();
-        Self { token, creation, expired_in }
+        Self {
+            token,
+            creation,
+            expired_in,
+            demande,
+            identite: Identite::default(),
+            dpop: None,
+        }
+    }
+
+    pub fn identifier(mut self, identite: Identite) -> Self {
+        self.identite = identite;
+        self
+    }
+
+    pub fn identite(&self) -> &Identite {
+        &self.identite
+    }
+
+    // Clé DPoP à laquelle le jeton est lié (RFC 9449)
+    pub fn lier(mut self, dpop: Option<CleDpop>) -> Self {
+        self.dpop = dpop;
+        self
+    }
+
+    pub fn dpop(&self) -> Option<&CleDpop> {
+        self.dpop.as_ref()
     }
 
     pub fn is_expired(&self) -> bool {
         self.creation.elapsed() >= self.expired_in
     }
 
+    pub fn expire_dans(&self) -> Duration {
+        self.expired_in.saturating_sub(self.creation.elapsed())
+    }
+
     pub fn secret(&self) -> &String {
         self.token.secret()
     }
+
+    pub fn demande(&self) -> &Demande {
+        &self.demande
+    }
 }
 
 #[derive(Clone)]
@@ -121,6 +231,8 @@ impl From<&str> for Fournisseur {
 }
 
 impl Fournisseur {
+    pub const TOUS: [Fournisseur; 2] = [Fournisseur::Microsoft, Fournisseur::Google];
+
     pub fn endpoints(&self) -> (&str, &str) {
         match self {
             Self::Microsoft => (AUTH_MS, TOKEN_MS),
@@ -141,4 +253,11 @@ impl Fournisseur {
             Self::Google => INFOS_GG,
         }
     }
+
+    pub fn discovery(&self) -> &str {
+        match self {
+            Self::Microsoft => DISCOVERY_MS,
+            Self::Google => DISCOVERY_GG,
+        }
+    }
 }
@@ -0,0 +1,39 @@
+use warp::http::{Error, Response, StatusCode};
+
+// Fichier statique compilé dans l'exécutable. L'ETag est calculé à la compilation.
+pub struct Fichier {
+    pub nom: &'static str,
+    pub contenu: &'static [u8],
+    pub etag: &'static str,
+}
+
+// Les noms ne sont pas versionnés: le navigateur revalide à chaque chargement et reçoit 304 si le fichier est inchangé
+pub fn repondre(fichier: &Fichier, if_none_match: Option<String>) -> Result<Response<&'static [u8]>, Error> {
+    let reponse = Response::builder().header("ETag", fichier.etag).header("Cache-Control", "no-cache");
+
+    if if_none_match.is_some_and(|etags| correspond(&etags, fichier.etag)) {
+        return reponse.status(StatusCode::NOT_MODIFIED).body(&[][..]);
+    }
+
+    reponse
+        .header("Content-Type", mime_guess::from_path(fichier.nom).first_or_octet_stream().as_ref())
+        .status(StatusCode::OK)
+        .body(fichier.contenu)
+}
+
+// If-None-Match utilise la comparaison faible (RFC 9110 13.1.2)
+fn correspond(etags: &str, etag: &str) -> bool {
+    etags.split(',').map(|e| e.trim().trim_start_matches("W/")).any(|e| e == "*" || e == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_none_match() {
+        assert!(correspond(r#""a", W/"b""#, r#""b""#));
+        assert!(correspond("*", r#""b""#));
+        assert!(!correspond(r#""a""#, r#""b""#));
+    }
+}
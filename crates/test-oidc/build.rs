@@ -0,0 +1,81 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+// Génère la table des fichiers statiques embarqués dans l'exécutable; vide sans la feature static-embarque
+fn main() -> Result<(), Box<dyn Error>> {
+    let repertoire = Path::new(&env::var("CARGO_MANIFEST_DIR")?).join("../../static");
+    println!("cargo:rerun-if-changed={}", repertoire.display());
+
+    let mut fichiers = Vec::new();
+    if env::var_os("CARGO_FEATURE_STATIC_EMBARQUE").is_some() && repertoire.is_dir() {
+        lister(&repertoire, &mut fichiers)?;
+    }
+    fichiers.sort();
+    verifier_references(&repertoire, &fichiers)?;
+
+    let mut table = String::from("pub static FICHIERS: &[server::Fichier] = &[\n");
+    for chemin in fichiers {
+        let nom = chemin.strip_prefix(&repertoire)?.to_string_lossy().replace('\\', "/");
+        let mut empreinte = DefaultHasher::new();
+        empreinte.write(&fs::read(&chemin)?);
+        println!("cargo:rerun-if-changed={}", chemin.display());
+        table.push_str(&format!(
+            "    server::Fichier {{ nom: {nom:?}, contenu: include_bytes!({:?}), etag: \"\\\"{:016x}\\\"\" }},\n",
+            chemin.canonicalize()?,
+            empreinte.finish()
+        ));
+    }
+    table.push_str("];\n");
+
+    fs::write(Path::new(&env::var("OUT_DIR")?).join("statique.rs"), table)?;
+    Ok(())
+}
+
+fn lister(repertoire: &Path, fichiers: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entree in fs::read_dir(repertoire)? {
+        let chemin = entree?.path();
+        if chemin.is_dir() {
+            println!("cargo:rerun-if-changed={}", chemin.display());
+            lister(&chemin, fichiers)?;
+        } else {
+            fichiers.push(chemin);
+        }
+    }
+    Ok(())
+}
+
+// Un fichier référencé par une page (src= ou href= relatif) mais absent de static/ serait servi en 404
+fn verifier_references(repertoire: &Path, fichiers: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    for page in fichiers.iter().filter(|f| f.extension().is_some_and(|e| e == "htm" || e == "html")) {
+        let contenu = fs::read_to_string(page)?;
+        for reference in references(&contenu) {
+            if reference.contains(':') || reference.starts_with(['/', '#']) {
+                continue;
+            }
+            let chemin = page.parent().unwrap_or(repertoire).join(reference);
+            if !fichiers.contains(&chemin) {
+                return Err(format!("{}: {reference} est référencé mais absent de {}", page.display(), repertoire.display()).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn references(contenu: &str) -> Vec<&str> {
+    let mut references = Vec::new();
+    for attribut in ["src=", "href="] {
+        for (debut, _) in contenu.match_indices(attribut) {
+            let reste = &contenu[debut + attribut.len()..];
+            let Some(guillemet) = reste.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                continue;
+            };
+            if let Some(fin) = reste[1..].find(guillemet) {
+                references.push(&reste[1..=fin]);
+            }
+        }
+    }
+    references
+}
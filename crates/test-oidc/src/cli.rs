@@ -0,0 +1,196 @@
+use crate::{dev_tls, statique};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+// Chaque option a son équivalent en variable d'environnement
+#[derive(Parser)]
+#[command(version, about = "Serveur de test OpenID Connect")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub commande: Option<Commande>,
+
+    /// Adresse d'écoute IP:Port
+    #[arg(long, env = "TEST_OIDC_ADRESSE", default_value = "127.0.0.1:8080", global = true)]
+    pub adresse: SocketAddr,
+
+    /// Répertoire des fichiers statiques, prioritaire sur les fichiers embarqués dans l'exécutable
+    #[arg(long = "static", env = "TEST_OIDC_STATIC", global = true)]
+    pub repertoire_static: Option<PathBuf>,
+
+    /// Certificat TLS (PEM)
+    #[arg(long, env = "TEST_OIDC_TLS_CERT", requires = "tls_cle", conflicts_with = "dev_tls", global = true)]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Clé privée TLS (PEM)
+    #[arg(long, env = "TEST_OIDC_TLS_CLE", requires = "tls_cert", global = true)]
+    pub tls_cle: Option<PathBuf>,
+
+    /// HTTPS avec un certificat localhost émis par une AC de développement générée au premier démarrage
+    #[arg(long, env = "TEST_OIDC_DEV_TLS", global = true)]
+    pub dev_tls: bool,
+
+    /// Répertoire de l'AC et du certificat de développement
+    #[arg(long, env = "TEST_OIDC_DEV_TLS_DIR", default_value = "dev-tls", global = true)]
+    pub dev_tls_dir: PathBuf,
+
+    /// Configuration des fournisseurs (JSON)
+    #[arg(long, env = "TEST_OIDC_FOURNISSEURS", global = true)]
+    pub fournisseurs: Option<PathBuf>,
+
+    /// Origine autorisée pour l'URI de redirection (répétable); par défaut, seul le loopback est autorisé
+    #[arg(long = "origine", env = "TEST_OIDC_ORIGINES", value_delimiter = ',', global = true)]
+    pub origines: Vec<String>,
+
+    /// Origine que /api et /client_credentials peuvent appeler avec un jeton (répétable); par défaut, toute adresse publique
+    #[arg(long = "origine-api", env = "TEST_OIDC_ORIGINES_API", value_delimiter = ',', global = true)]
+    pub origines_api: Vec<String>,
+
+    /// Niveau ou directives de filtrage des traces
+    #[arg(long, env = "TEST_OIDC_LOG", default_value = "info", global = true)]
+    pub log: String,
+
+    /// Format des traces
+    #[arg(long, env = "TEST_OIDC_LOG_FORMAT", value_enum, default_value_t = FormatLog::Texte, global = true)]
+    pub log_format: FormatLog,
+
+    /// Durée de vie en secondes d'un jeton sans expires_in
+    #[arg(long, env = "TEST_OIDC_EXPIRATION_DEFAUT", default_value_t = 60, global = true)]
+    pub expiration_defaut: u64,
+
+    /// Clé de session id:clé (AES-256 en base64, répétable): les sessions sont alors chiffrées dans le cookie.
+    /// La première clé chiffre, les suivantes déchiffrent encore les cookies émis avant une rotation.
+    #[arg(
+        long = "cle-session",
+        env = "TEST_OIDC_CLES_SESSION",
+        value_delimiter = ',',
+        global = true,
+        hide_env_values = true
+    )]
+    pub cles_session: Vec<String>,
+
+    /// Content-Security-Policy des réponses (vide: sans entête); la page n'exige ni 'unsafe-inline' ni 'unsafe-eval'
+    #[arg(long, env = "TEST_OIDC_CSP", default_value = server::CSP_DEFAUT, global = true)]
+    pub csp: String,
+
+    /// Durée max-age de Strict-Transport-Security en secondes, émis seulement en HTTPS (0: sans entête)
+    #[arg(long, env = "TEST_OIDC_HSTS_MAX_AGE", default_value_t = 31_536_000, global = true)]
+    pub hsts_max_age: u64,
+
+    /// X-Frame-Options des réponses (vide: sans entête)
+    #[arg(long, env = "TEST_OIDC_FRAME_OPTIONS", default_value = server::FRAME_OPTIONS_DEFAUT, global = true)]
+    pub frame_options: String,
+
+    /// Referrer-Policy des réponses (vide: sans entête)
+    #[arg(long, env = "TEST_OIDC_REFERRER_POLICY", default_value = server::REFERRER_POLICY_DEFAUT, global = true)]
+    pub referrer_policy: String,
+
+    /// Permissions-Policy des réponses (vide: sans entête)
+    #[arg(long, env = "TEST_OIDC_PERMISSIONS_POLICY", default_value = server::PERMISSIONS_POLICY_DEFAUT, global = true)]
+    pub permissions_policy: String,
+
+    /// Requêtes par minute acceptées par IP sur /userinfos, /auth et /backchannel-logout (0: sans limite)
+    #[arg(long, env = "TEST_OIDC_LIMITE_IP", default_value_t = 120, global = true)]
+    pub limite_ip: u32,
+
+    /// Requêtes par minute acceptées par cookie de session sur /userinfos et /auth (0: sans limite)
+    #[arg(long, env = "TEST_OIDC_LIMITE_SESSION", default_value_t = 30, global = true)]
+    pub limite_session: u32,
+
+    /// Délai en secondes accordé aux requêtes en cours lors de l'arrêt
+    #[arg(long, env = "TEST_OIDC_DELAI_ARRET", default_value_t = 30, global = true)]
+    pub delai_arret: u64,
+
+    /// Fichier où les sessions authentifiées sont conservées entre deux démarrages
+    #[arg(long, env = "TEST_OIDC_FICHIER_SESSIONS", global = true)]
+    pub fichier_sessions: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Commande {
+    /// Valide la configuration sans démarrer le serveur
+    CheckConfig,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FormatLog {
+    Texte,
+    Json,
+}
+
+impl Cli {
+    pub fn tls(&self) -> Option<(&PathBuf, &PathBuf)> {
+        self.tls_cert.as_ref().zip(self.tls_cle.as_ref())
+    }
+
+    pub fn filtre_log(&self) -> Result<EnvFilter, Box<dyn Error>> {
+        Ok(EnvFilter::try_new(&self.log)?)
+    }
+
+    // Charge la configuration dans le serveur
+    pub fn configurer(&mut self) -> Result<(), Box<dyn Error>> {
+        self.charger(false)
+    }
+
+    // check-config: même contrôle que configurer, sans écrire l'AC ni le certificat de --dev-tls
+    pub fn verifier(&mut self) -> Result<(), Box<dyn Error>> {
+        self.charger(true)
+    }
+
+    fn charger(&mut self, verification: bool) -> Result<(), Box<dyn Error>> {
+        match &self.repertoire_static {
+            Some(repertoire) if !repertoire.is_dir() => {
+                return Err(format!("{} n'existe pas ou n'est pas accessible", repertoire.to_string_lossy()).into());
+            }
+            None if statique::FICHIERS.is_empty() => return Err("aucun fichier statique embarqué: --static est requis".into()),
+            _ => {}
+        }
+
+        if self.dev_tls {
+            let fichiers = match verification {
+                true => dev_tls::verifier(&self.dev_tls_dir)?,
+                false => Some(dev_tls::preparer(&self.dev_tls_dir)?),
+            };
+            if let Some((cert, cle)) = fichiers {
+                self.tls_cert = Some(cert);
+                self.tls_cle = Some(cle);
+            }
+        }
+
+        if let Some((cert, cle)) = self.tls() {
+            server::configurer_tls(cert, cle)?;
+        }
+        // Avec --dev-tls, le certificat pas encore émis sera servi au démarrage
+        let https = self.dev_tls || self.tls().is_some();
+        server::configurer_cookies(https);
+        server::configurer_entetes(
+            &self.csp,
+            if https { self.hsts_max_age } else { 0 },
+            &self.frame_options,
+            &self.referrer_policy,
+            &self.permissions_policy,
+        )?;
+        server::configurer_origines(&self.origines)?;
+        server::configurer_origines_api(&self.origines_api)?;
+        server::configurer_cles_session(&self.cles_session)?;
+        if !self.cles_session.is_empty() && self.fichier_sessions.is_some() {
+            return Err("fichier-sessions est sans objet avec des sessions chiffrées dans le cookie".into());
+        }
+
+        // Configuration optionnelle des fournisseurs (méthode d'authentification au token endpoint, clé privée)
+        if let Some(chemin) = &self.fournisseurs {
+            server::configurer(chemin)?;
+        }
+
+        if self.expiration_defaut == 0 {
+            return Err("expiration-defaut doit être supérieure à 0".into());
+        }
+        server::configurer_sessions(Duration::from_secs(self.expiration_defaut));
+        server::configurer_limites(self.limite_ip, self.limite_session);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType};
+use std::error::Error;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const CA: &str = "ca.pem";
+const CA_CLE: &str = "ca-key.pem";
+const CERT: &str = "server.pem";
+const CERT_CLE: &str = "server-key.pem";
+
+// L'AC locale est créée une seule fois pour n'avoir à l'approuver qu'une fois.
+// Le certificat localhost est réémis si l'AC change ou s'il est absent.
+pub fn preparer(repertoire: &Path) -> Result<(PathBuf, PathBuf), Box<dyn Error>> {
+    fs::create_dir_all(repertoire).map_err(|e| format!("{}: {e}", repertoire.to_string_lossy()))?;
+    let (ca, ca_cle, nouvelle_ca) = match fs::read_to_string(repertoire.join(CA_CLE)) {
+        Ok(pem) if repertoire.join(CA).is_file() => {
+            let ca_cle = KeyPair::from_pem(&pem)?;
+            (autorite(&ca_cle)?, ca_cle, false)
+        }
+        _ => {
+            let ca_cle = KeyPair::generate()?;
+            let ca = autorite(&ca_cle)?;
+            ecrire(&repertoire.join(CA), &ca.pem())?;
+            ecrire(&repertoire.join(CA_CLE), &ca_cle.serialize_pem())?;
+            (ca, ca_cle, true)
+        }
+    };
+
+    let (cert, cle) = (repertoire.join(CERT), repertoire.join(CERT_CLE));
+    if nouvelle_ca || !cert.is_file() || !cle.is_file() {
+        let cle_localhost = KeyPair::generate()?;
+        let localhost = localhost()?.signed_by(&cle_localhost, &ca, &ca_cle)?;
+        ecrire(&cert, &localhost.pem())?;
+        ecrire(&cle, &cle_localhost.serialize_pem())?;
+        info!("certificat localhost émis");
+    }
+
+    let chemin_ca = fs::canonicalize(repertoire.join(CA))?;
+    println!("Autorité de certification de développement à approuver: {}", chemin_ca.to_string_lossy());
+
+    Ok((cert, cle))
+}
+
+// check-config: les fichiers présents sont contrôlés, rien n'est créé ni réémis
+pub fn verifier(repertoire: &Path) -> Result<Option<(PathBuf, PathBuf)>, Box<dyn Error>> {
+    if repertoire.exists() && !repertoire.is_dir() {
+        return Err(format!("{} n'est pas un répertoire", repertoire.to_string_lossy()).into());
+    }
+    let chemin_ca_cle = repertoire.join(CA_CLE);
+    match fs::read_to_string(&chemin_ca_cle) {
+        Ok(pem) => {
+            KeyPair::from_pem(&pem).map_err(|e| format!("{}: {e}", chemin_ca_cle.to_string_lossy()))?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("{}: {e}", chemin_ca_cle.to_string_lossy()).into()),
+    }
+
+    let (cert, cle) = (repertoire.join(CERT), repertoire.join(CERT_CLE));
+    if repertoire.join(CA).is_file() && cert.is_file() && cle.is_file() {
+        Ok(Some((cert, cle)))
+    } else {
+        println!(
+            "AC ou certificat de développement absent de {}: émis au démarrage",
+            repertoire.to_string_lossy()
+        );
+        Ok(None)
+    }
+}
+
+// Les paramètres sont fixes: l'AC est reconstituée à partir de sa clé pour signer
+fn autorite(cle: &KeyPair) -> Result<Certificate, rcgen::Error> {
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, "test-oidc AC de développement");
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+    params.self_signed(cle)
+}
+
+fn localhost() -> Result<CertificateParams, rcgen::Error> {
+    let mut params = CertificateParams::new(vec!["localhost".to_owned()])?;
+    params.distinguished_name.push(DnType::CommonName, "localhost");
+    params.subject_alt_names.push(SanType::IpAddress(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    params.subject_alt_names.push(SanType::IpAddress(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    params.use_authority_key_identifier_extension = true;
+    Ok(params)
+}
+
+// Les clés privées ne sont lisibles que par le propriétaire
+fn ecrire(chemin: &Path, pem: &str) -> Result<(), Box<dyn Error>> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut fichier = options.open(chemin).map_err(|e| format!("{}: {e}", chemin.to_string_lossy()))?;
+    std::io::Write::write_all(&mut fichier, pem.as_bytes())?;
+    Ok(())
+}
@@ -1,54 +1,112 @@
+use clap::Parser;
+use cli::{Cli, Commande, FormatLog};
 use server::filters::*;
-use std::env::{args, Args};
 use std::error::Error;
-use std::net::SocketAddr;
-use std::path::PathBuf;
-use warp::Filter;
-
-fn parse_args(args: &mut Args) -> Result<(SocketAddr, PathBuf, Option<PathBuf>), Box<dyn Error>> {
-    let addr = match args.nth(1) {
-        Some(arg) => arg.parse::<SocketAddr>()?,
-        None => return Err("IP:Port est manquant".into()),
-    };
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+use warp::{Filter, Reply};
 
-    let path_static = match args.next() {
-        Some(arg) => arg.parse::<PathBuf>()?,
-        None => return Err("Le chemin du répertoire static est manquant".into()),
-    };
+mod cli;
+mod dev_tls;
+mod statique;
+mod tls;
 
-    if !path_static.is_dir() {
-        return Err(format!("{} n'existe pas ou n'est pas accessible", path_static.to_string_lossy()).into());
+fn initialiser_traces(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let traces = tracing_subscriber::fmt().with_env_filter(cli.filtre_log()?);
+    match cli.log_format {
+        FormatLog::Json => traces.json().init(),
+        FormatLog::Texte => traces.init(),
     }
+    Ok(())
+}
 
-    let path_tls = match args.next() {
-        Some(arg) => {
-            let p = arg.parse::<PathBuf>()?;
-            if !p.is_dir() {
-                return Err(format!("{} n'existe pas ou n'est pas accessible", p.to_string_lossy()).into());
-            }
-            Some(p)
+async fn signal_arret() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).expect("SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = sigterm.recv() => {},
         }
-        None => None,
-    };
-
-    Ok((addr, path_static, path_tls))
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let (addr, path_static, path_tls) = parse_args(&mut args())?;
-    let routes = static_file(path_static).or(userinfos()).or(auth());
-
-    let server = warp::serve(routes);
-    if let Some(p) = path_tls {
-        server
-            .tls()
-            .cert_path(p.join("server.pem"))
-            .key_path(p.join("server-key.pem"))
-            .run(addr)
-            .await;
-    } else {
-        server.run(addr).await;
+    let mut cli = Cli::parse();
+    if let Some(Commande::CheckConfig) = cli.commande {
+        cli.filtre_log()?;
+        cli.verifier()?;
+        println!("Configuration valide");
+        return Ok(());
+    }
+
+    initialiser_traces(&cli)?;
+    cli.configurer()?;
+    if let Some(chemin) = &cli.fichier_sessions {
+        info!(sessions = server::restaurer_sessions(chemin)?, "sessions restaurées");
+    }
+
+    // Un fichier absent du répertoire est servi depuis l'exécutable
+    let fichiers = match cli.repertoire_static.clone() {
+        Some(repertoire) => static_file(repertoire)
+            .or(static_embarque(statique::FICHIERS))
+            .map(Reply::into_response)
+            .boxed(),
+        None => static_embarque(statique::FICHIERS).map(Reply::into_response).boxed(),
+    };
+    let routes = fichiers
+        .or(userinfos())
+        .or(auth())
+        .or(token())
+        .or(client_credentials())
+        .or(api())
+        .or(statut())
+        .or(backchannel_logout())
+        .or(frontchannel_logout())
+        .or(metrics())
+        .or(healthz())
+        .or(readyz())
+        .map(securite);
+
+    // Au signal, le serveur cesse d'accepter des connexions et termine les requêtes en cours
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        signal_arret().await;
+        info!("arrêt demandé");
+        let _ = tx.send(true);
+    });
+    let mut rx_arret = rx.clone();
+    let arret = async move {
+        let _ = rx_arret.wait_for(|arret| *arret).await;
+    };
+    let mut rx_delai = rx;
+    let delai_arret = Duration::from_secs(cli.delai_arret);
+    let delai = async move {
+        let _ = rx_delai.wait_for(|arret| *arret).await;
+        tokio::time::sleep(delai_arret).await;
+    };
+
+    let serveur = async {
+        match cli.tls() {
+            Some((cert, cle)) => tls::servir(routes, cli.adresse, cert, cle, arret).await,
+            None => {
+                let (_, serveur) = warp::serve(routes).try_bind_with_graceful_shutdown(cli.adresse, arret)?;
+                serveur.await;
+                Ok(())
+            }
+        }
+    };
+    tokio::select! {
+        resultat = serveur => resultat?,
+        _ = delai => warn!("délai d'arrêt dépassé, requêtes en cours interrompues"),
+    }
+
+    if let Some(chemin) = &cli.fichier_sessions {
+        info!(sessions = server::sauvegarder_sessions(chemin)?, "sessions sauvegardées");
     }
 
     Ok(())
@@ -0,0 +1,2 @@
+// Fichiers du répertoire static embarqués à la compilation (build.rs)
+include!(concat!(env!("OUT_DIR"), "/statique.rs"));
@@ -0,0 +1,116 @@
+use hyper::server::accept;
+use hyper::service::{make_service_fn, service_fn, Service};
+use hyper::{Body, Request};
+use std::convert::Infallible;
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+use warp::{Filter, Rejection, Reply};
+
+const INTERVALLE_SURVEILLANCE: Duration = Duration::from_secs(5);
+
+// Le certificat courant est remplacé sans fermer le listener
+#[derive(Debug)]
+struct Resolveur(RwLock<Arc<CertifiedKey>>);
+
+impl ResolvesServerCert for Resolveur {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.read().expect("Failed due to poisoned lock").clone())
+    }
+}
+
+fn modifie(chemin: &Path) -> Option<SystemTime> {
+    std::fs::metadata(chemin).and_then(|m| m.modified()).ok()
+}
+
+// Un certificat renouvelé est chargé dès que le certificat et la clé sont valides
+async fn surveiller(resolveur: Arc<Resolveur>, cert: PathBuf, cle: PathBuf) {
+    let mut versions = (modifie(&cert), modifie(&cle));
+    let mut intervalle = tokio::time::interval(INTERVALLE_SURVEILLANCE);
+    loop {
+        intervalle.tick().await;
+        let courantes = (modifie(&cert), modifie(&cle));
+        if courantes == versions {
+            continue;
+        }
+        versions = courantes;
+
+        match server::configurer_tls(&cert, &cle) {
+            Ok(certifie) => {
+                *resolveur.0.write().expect("Failed due to poisoned lock") = Arc::new(certifie);
+                info!("certificat TLS rechargé");
+            }
+            Err(e) => warn!("certificat TLS non rechargé: {e}"),
+        }
+    }
+}
+
+pub async fn servir<F, R>(routes: F, adresse: SocketAddr, cert: &Path, cle: &Path, arret: impl Future<Output = ()>) -> Result<(), Box<dyn Error>>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let resolveur = Arc::new(Resolveur(RwLock::new(Arc::new(server::configurer_tls(cert, cle)?))));
+    let mut config = ServerConfig::builder().with_no_client_auth().with_cert_resolver(resolveur.clone());
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    tokio::spawn(surveiller(resolveur, cert.to_owned(), cle.to_owned()));
+
+    // Les poignées de main se font en parallèle pour qu'un client lent ne bloque pas les autres
+    let listener = TcpListener::bind(adresse).await?;
+    let (tx, mut rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let tcp = tokio::select! {
+                _ = tx.closed() => break,
+                tcp = listener.accept() => tcp,
+            };
+            match tcp {
+                Ok((tcp, _)) => {
+                    let (acceptor, tx) = (acceptor.clone(), tx.clone());
+                    tokio::spawn(async move {
+                        match acceptor.accept(tcp).await {
+                            Ok(tls) => {
+                                let _ = tx.send(tls).await;
+                            }
+                            Err(e) => debug!("poignée de main TLS: {e}"),
+                        }
+                    });
+                }
+                Err(e) => warn!("{e}"),
+            }
+        }
+    });
+
+    // warp::addr::remote() n'est renseigné que par warp::serve: l'adresse du client est passée en extension
+    let service = warp::service(routes);
+    let make_service = make_service_fn(move |tls: &TlsStream<TcpStream>| {
+        let adresse = tls.get_ref().0.peer_addr().ok();
+        let mut service = service.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |mut requete: Request<Body>| {
+                if let Some(adresse) = adresse {
+                    requete.extensions_mut().insert(adresse);
+                }
+                service.call(requete)
+            }))
+        }
+    });
+    hyper::Server::builder(accept::poll_fn(move |cx| rx.poll_recv(cx).map(|tls| tls.map(Ok::<_, std::io::Error>))))
+        .serve(make_service)
+        .with_graceful_shutdown(arret)
+        .await?;
+
+    Ok(())
+}
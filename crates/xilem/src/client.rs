@@ -0,0 +1,32 @@
+use crate::Fournisseur;
+use anyhow::Error;
+use commun::assertion::{self, AuthClient, MethodeAuth};
+use oauth2::basic::BasicClient;
+use oauth2::{AuthType, AuthUrl, ClientId, ClientSecret, TokenUrl};
+
+// Pour les méthodes JWT, le secret n'est pas transmis: l'assertion le remplace
+pub fn client_oauth(f: &Fournisseur, auth: &AuthClient) -> Result<BasicClient, Error> {
+    let (id, secret) = f.secrets();
+    let (url_auth, url_token) = f.endpoints();
+
+    let id = ClientId::new(id.to_owned());
+    let url_auth = AuthUrl::new(url_auth.to_owned())?;
+    let url_token = TokenUrl::new(url_token.to_owned())?;
+    let secret = ClientSecret::new(secret.to_owned());
+
+    let client = match auth.methode {
+        MethodeAuth::ClientSecretPost => BasicClient::new(id, Some(secret), url_auth, Some(url_token)).set_auth_type(AuthType::RequestBody),
+        MethodeAuth::ClientSecretBasic => BasicClient::new(id, Some(secret), url_auth, Some(url_token)).set_auth_type(AuthType::BasicAuth),
+        MethodeAuth::ClientSecretJwt | MethodeAuth::PrivateKeyJwt => {
+            BasicClient::new(id, None, url_auth, Some(url_token)).set_auth_type(AuthType::RequestBody)
+        }
+    };
+
+    Ok(client)
+}
+
+pub fn parametres_assertion(f: &Fournisseur, auth: &AuthClient) -> Result<Vec<(&'static str, String)>, Error> {
+    let (id, secret) = f.secrets();
+    let (_, url_token) = f.endpoints();
+    assertion::parametres_assertion(auth, id, secret, url_token)
+}
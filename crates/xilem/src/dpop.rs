@@ -0,0 +1,54 @@
+use commun::dpop;
+use oauth2::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use oauth2::ureq::{http_client, Error};
+use oauth2::{HttpRequest, HttpResponse};
+use std::io::Read;
+
+// OAuth 2.0 Demonstrating Proof of Possession (RFC 9449): preuves et nonces dans commun::dpop
+pub use commun::dpop::{envoyer, liaison, CleDpop};
+
+// Client HTTP du token endpoint: la requête porte une preuve DPoP si une clé est fournie.
+// Les réponses d'erreur sont transmises avec leurs entêtes pour lire DPoP-Nonce.
+pub fn requete_jeton(cle: Option<&CleDpop>) -> impl Fn(HttpRequest) -> Result<HttpResponse, Error> + '_ {
+    move |requete: HttpRequest| {
+        let Some(cle) = cle else {
+            return http_client(requete);
+        };
+
+        let url = requete.url.to_string();
+        let mut rejouee = false;
+        loop {
+            let appel = requete
+                .headers
+                .iter()
+                .filter_map(|(nom, valeur)| Some((nom.as_str(), valeur.to_str().ok()?)))
+                .fold(ureq::request(requete.method.as_str(), &url), |appel, (nom, valeur)| {
+                    appel.set(nom, valeur)
+                })
+                .set("DPoP", &cle.preuve(requete.method.as_str(), &url, None));
+            let reponse = match appel.send_bytes(&requete.body) {
+                Ok(reponse) | Err(ureq::Error::Status(_, reponse)) => reponse,
+                Err(e) => return Err(Error::Ureq(Box::new(e))),
+            };
+
+            let statut = reponse.status();
+            let nonce = reponse.header("DPoP-Nonce").map(str::to_owned);
+            let mut entetes = HeaderMap::new();
+            for nom in reponse.headers_names() {
+                if let (Ok(nom), Some(Ok(valeur))) = (HeaderName::from_bytes(nom.as_bytes()), reponse.header(&nom).map(HeaderValue::from_str)) {
+                    entetes.append(nom, valeur);
+                }
+            }
+            let mut corps = Vec::new();
+            reponse.into_reader().read_to_end(&mut corps)?;
+            if rejouee || !dpop::rejouer_as(&url, statut, nonce.as_deref(), &corps) {
+                return Ok(HttpResponse {
+                    status_code: StatusCode::from_u16(statut).map_err(|e| Error::Http(e.into()))?,
+                    headers: entetes,
+                    body: corps,
+                });
+            }
+            rejouee = true;
+        }
+    }
+}
@@ -1,20 +1,31 @@
 //use masonry::vello::peniko::{Format, Image as ImageBuf};
 //use masonry::widget::{CrossAxisAlignment, FillStrat, Image, MainAxisAlignment};
 use masonry::Color;
-use xilem::view::{button, checkbox, flex, label};
+use xilem::view::{button, checkbox, flex, label, textbox};
 use xilem::Axis;
 
 use anyhow::{anyhow, Result};
+use commun::api::{self, Methode, ReponseApi, RequeteApi};
+use commun::assertion::{AuthClient, MethodeAuth, ModePar};
+use commun::autorisation::{Demande, SCOPES};
+use commun::credentials::ClientCredentials;
+use commun::jwt;
 use winit::dpi::LogicalSize;
 use winit::window::Window;
 use xilem::{MasonryView, Xilem};
 
+mod client;
+mod credentials;
+mod dpop;
 mod table;
 use serde_json::value::Value;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fmt, sync::Arc};
 use table::{table, TableData};
 
 mod pkce;
+use credentials::client_credentials;
 use pkce::Pkce;
 
 const ID_MS: &str = include_str!("../../../secrets/clientid.microsoft");
@@ -27,18 +38,47 @@ const TOKEN_MS: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/
 const TOKEN_GG: &str = "https://oauth2.googleapis.com/token";
 const INFOS_MS: &str = "https://graph.microsoft.com/oidc/userinfo";
 const INFOS_GG: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+const DISCOVERY_MS: &str = "https://login.microsoftonline.com/consumers/v2.0/.well-known/openid-configuration";
+const DISCOVERY_GG: &str = "https://accounts.google.com/.well-known/openid-configuration";
 
 #[derive(Clone)]
 struct AppData {
     radio_fournisseur: Fournisseur,
     label_fournisseur: String,
     secret: Option<Pkce>,
+    // Scopes cochés, autres scopes et paramètres additionnels de la requête d'autorisation
+    scopes: [bool; SCOPES.len()],
+    autres_scopes: String,
+    parametres: String,
+    // Authentification au token endpoint choisie pour chaque fournisseur
+    auth_clients: HashMap<Fournisseur, AuthClient>,
+    client_credentials: ClientCredentials,
+    requete_api: RequeteApi,
+    titre_infos: String,
     infos: Arc<TableData>,
+    // Réponse de la ressource protégée appelée avec le jeton client_credentials ou entêtes de la réponse de l'API
+    ressource: Arc<TableData>,
+    // Inspection du jeton d'accès; expiration en secondes depuis l'époque Unix
+    titre_jeton: String,
+    jeton: Arc<TableData>,
+    expiration: Option<u64>,
+    // Liaison DPoP: cnf.jkt du jeton comparé à l'empreinte de la clé
+    liaison: String,
     //    en_traitement: bool,
     erreur: String,
 }
 
-#[derive(Clone, PartialEq)]
+impl AppData {
+    fn auth(&self) -> AuthClient {
+        self.auth_clients.get(&self.radio_fournisseur).cloned().unwrap_or_default()
+    }
+
+    fn auth_mut(&mut self) -> &mut AuthClient {
+        self.auth_clients.entry(self.radio_fournisseur.clone()).or_default()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Fournisseur {
     Microsoft,
     Google,
@@ -75,9 +115,46 @@ impl Fournisseur {
             Self::Google => INFOS_GG,
         }
     }
+
+    fn discovery(&self) -> &str {
+        match self {
+            Self::Microsoft => DISCOVERY_MS,
+            Self::Google => DISCOVERY_GG,
+        }
+    }
 }
 
 fn app_logic(data: &mut AppData) -> impl MasonryView<AppData> {
+    // Authentification au token endpoint et PAR du fournisseur choisi
+    let auth_client = flex((
+        // Un clic passe à la méthode suivante
+        button(format!("Authentification: {}", data.auth().methode), |data: &mut AppData| {
+            let auth = data.auth_mut();
+            let suivante = MethodeAuth::ALL
+                .iter()
+                .position(|&m| m == auth.methode)
+                .map_or(0, |i| (i + 1) % MethodeAuth::ALL.len());
+            auth.methode = MethodeAuth::ALL[suivante];
+        }),
+        button(data.auth().par.to_string(), |data: &mut AppData| {
+            let auth = data.auth_mut();
+            let suivant = ModePar::ALL
+                .iter()
+                .position(|&p| p == auth.par)
+                .map_or(0, |i| (i + 1) % ModePar::ALL.len());
+            auth.par = ModePar::ALL[suivant];
+        }),
+        checkbox("Jeton lié par DPoP", data.auth().dpop, |data: &mut AppData, dpop| {
+            data.auth_mut().dpop = dpop
+        }),
+        checkbox("Requête d'autorisation signée (JAR)", data.auth().jar, |data: &mut AppData, jar| {
+            data.auth_mut().jar = jar
+        }),
+        label("Clé privée PEM pour private_key_jwt ou JAR (RSA ou EC P-256):").color(Color::ORANGE),
+        textbox(data.auth().cle, |data: &mut AppData, cle| data.auth_mut().cle = cle),
+    ))
+    .direction(Axis::Vertical);
+
     let oidc = flex((
         label("OpenID Connect").color(Color::ORANGE),
         label("Fournisseurs:").color(Color::ORANGE),
@@ -94,13 +171,33 @@ fn app_logic(data: &mut AppData) -> impl MasonryView<AppData> {
                 }
             },
         ),
+        label("Scopes:").color(Color::ORANGE),
+        flex(
+            SCOPES
+                .iter()
+                .enumerate()
+                .map(|(i, scope)| checkbox(*scope, data.scopes[i], move |data: &mut AppData, coche| data.scopes[i] = coche))
+                .collect::<Vec<_>>(),
+        )
+        .direction(Axis::Horizontal),
+        textbox(data.autres_scopes.clone(), |data: &mut AppData, scopes| data.autres_scopes = scopes),
+        label("Paramètres: prompt=consent&login_hint=...").color(Color::ORANGE),
+        textbox(data.parametres.clone(), |data: &mut AppData, parametres| data.parametres = parametres),
+        auth_client,
         button("Userinfos", |data: &mut AppData| {
             let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
-            let infos = rt.block_on(get_infos(data.radio_fournisseur.clone(), data.secret.clone()));
+            let infos = rt.block_on(get_infos(data.radio_fournisseur.clone(), data.secret.clone(), demande(data), data.auth()));
             match infos {
                 Ok((infos, secret)) => {
+                    data.titre_infos = format!("Userinfos {}", data.label_fournisseur);
                     data.infos = Arc::new(infos.expect("infos absentes"));
+                    data.ressource = Arc::new(TableData::default());
                     data.secret = secret;
+                    // Le jeton a pu être renouvelé
+                    data.titre_jeton = String::new();
+                    data.jeton = Arc::new(TableData::default());
+                    data.expiration = None;
+                    data.liaison = String::new();
                 }
                 Err(err) => {
                     data.erreur = err.to_string();
@@ -108,34 +205,185 @@ fn app_logic(data: &mut AppData) -> impl MasonryView<AppData> {
                 }
             }
         }),
+        button("Jeton", |data: &mut AppData| {
+            data.erreur = String::new();
+            inspecter(data);
+        }),
+    ))
+    .direction(Axis::Vertical);
+
+    let client_credentials = flex((
+        label("Client credentials").color(Color::ORANGE),
+        label("Scopes: https://graph.microsoft.com/.default").color(Color::ORANGE),
+        textbox(data.client_credentials.scopes.clone(), |data: &mut AppData, scopes| {
+            data.client_credentials.scopes = scopes
+        }),
+        label("Resource: https://api.exemple.com").color(Color::ORANGE),
+        textbox(data.client_credentials.resource.clone(), |data: &mut AppData, resource| {
+            data.client_credentials.resource = resource
+        }),
+        label("Ressource protégée (GET): https://graph.microsoft.com/v1.0/users").color(Color::ORANGE),
+        textbox(data.client_credentials.url.clone(), |data: &mut AppData, url| {
+            data.client_credentials.url = url
+        }),
+        button("Client credentials", |data: &mut AppData| {
+            data.erreur = String::new();
+            match client_credentials(&data.radio_fournisseur, &data.client_credentials, &data.auth()) {
+                Ok((jeton, ressource)) => {
+                    data.titre_infos = format!("Client credentials {}", data.label_fournisseur);
+                    data.infos = Arc::new(jeton.into());
+                    data.ressource = Arc::new(ressource.map(TableData::from).unwrap_or_default());
+                }
+                Err(err) => data.erreur = err.to_string(),
+            }
+        }),
     ))
     .direction(Axis::Vertical);
 
+    // Le corps de la réponse va dans la table des infos, ses entêtes dans celle de la ressource
+    let api = flex((
+        label("API").color(Color::ORANGE),
+        // Un clic passe à la méthode suivante
+        button(format!("Méthode: {}", data.requete_api.methode), |data: &mut AppData| {
+            let methode = data.requete_api.methode;
+            let suivante = Methode::ALL
+                .iter()
+                .position(|&m| m == methode)
+                .map_or(0, |i| (i + 1) % Methode::ALL.len());
+            data.requete_api.methode = Methode::ALL[suivante];
+        }),
+        label("URL: https://graph.microsoft.com/v1.0/me").color(Color::ORANGE),
+        textbox(data.requete_api.url.clone(), |data: &mut AppData, url| data.requete_api.url = url),
+        label("Entêtes (Nom: valeur), une par ligne:").color(Color::ORANGE),
+        textbox(data.requete_api.entetes.clone(), |data: &mut AppData, entetes| {
+            data.requete_api.entetes = entetes
+        }),
+        label("Corps:").color(Color::ORANGE),
+        textbox(data.requete_api.corps.clone(), |data: &mut AppData, corps| data.requete_api.corps = corps),
+        button("Envoyer", |data: &mut AppData| {
+            data.erreur = String::new();
+            if data.requete_api.url.trim().is_empty() {
+                data.erreur = "L'URL est requise".to_owned();
+                return;
+            }
+            let requete = RequeteApi {
+                corps: data.requete_api.corps.trim_end().to_owned(),
+                ..data.requete_api.clone()
+            };
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            match rt.block_on(appeler_api(
+                data.radio_fournisseur.clone(),
+                data.secret.clone(),
+                demande(data),
+                data.auth(),
+                requete,
+            )) {
+                Ok((reponse, secret)) => {
+                    data.titre_infos = format!("API {}: statut {}", data.label_fournisseur, reponse.statut);
+                    data.infos = Arc::new(reponse.corps.into());
+                    data.ressource = Arc::new(reponse.entetes.into());
+                    data.secret = Some(secret);
+                }
+                Err(err) => data.erreur = err.to_string(),
+            }
+        }),
+    ))
+    .direction(Axis::Vertical);
+
+    // Le compteur est recalculé à chaque reconstruction de la vue
     let infos = flex((
-        label(format!("Userinfos {}", data.label_fournisseur)).color(Color::ORANGE),
+        label(data.titre_infos.clone()).color(Color::ORANGE),
         table(data.infos.clone()).header_text_brush(Color::ORANGE),
+        table(data.ressource.clone()).header_text_brush(Color::ORANGE),
+        label(data.titre_jeton.clone()).color(Color::ORANGE),
+        label(compteur(data.expiration)),
+        label(data.liaison.clone()),
+        table(data.jeton.clone()).header_text_brush(Color::ORANGE),
     ))
     .direction(Axis::Vertical);
 
     flex((
-        flex((oidc, infos)).direction(Axis::Horizontal),
+        flex((oidc, client_credentials, api, infos)).direction(Axis::Horizontal),
         label(data.erreur.clone()).color(Color::RED),
     ))
     .direction(Axis::Vertical)
 }
 
-async fn get_infos(fournisseur: Fournisseur, secret: Option<Pkce>) -> Result<(Option<TableData>, Option<Pkce>)> {
-    let secret = match secret {
-        Some(pkce) if pkce.is_expired() => Some(Pkce::new(&fournisseur).await?),
-        Some(pkce) => Some(pkce),
-        None => Some(Pkce::new(&fournisseur).await?),
+fn demande(data: &AppData) -> Demande {
+    let scopes = SCOPES
+        .iter()
+        .zip(data.scopes)
+        .filter(|&(_, coche)| coche)
+        .map(|(scope, _)| scope.to_string());
+    Demande::saisie(scopes, &data.autres_scopes, &data.parametres)
+}
+
+fn maintenant() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// exp fait foi pour un JWT, sinon la durée de vie retournée avec le jeton
+fn inspecter(data: &mut AppData) {
+    let Some(pkce) = data.secret.as_ref() else {
+        data.erreur = "Aucun jeton: cliquez d'abord sur Userinfos".to_owned();
+        return;
     };
+    let expiration = maintenant() + pkce.expire_dans().as_secs();
+    match jwt::inspecter(pkce.secret()) {
+        jwt::FormatJeton::Jwt { entete, charge } => {
+            data.titre_jeton = "Jeton d'accès JWT".to_owned();
+            data.expiration = Some(charge.get("exp").and_then(Value::as_u64).unwrap_or(expiration));
+            data.liaison = pkce.dpop().map(|cle| dpop::liaison(cle, Some(&charge))).unwrap_or_default();
+            data.jeton = Arc::new(jwt::lignes(&entete, &charge).into());
+        }
+        jwt::FormatJeton::Opaque => {
+            data.titre_jeton = "Jeton d'accès opaque: il ne peut pas être décodé par le client".to_owned();
+            data.expiration = Some(expiration);
+            data.liaison = pkce.dpop().map(|cle| dpop::liaison(cle, None)).unwrap_or_default();
+            data.jeton = Arc::new(TableData::default());
+        }
+    }
+}
+
+fn compteur(expiration: Option<u64>) -> String {
+    match expiration.map(|exp| exp.saturating_sub(maintenant())) {
+        Some(0) => "Expiré".to_owned(),
+        Some(restant) => format!("Expire dans {}:{:02}", restant / 60, restant % 60),
+        None => String::new(),
+    }
+}
+
+// Le jeton est réutilisé tant qu'il n'est pas expiré et que la demande est la même
+async fn jeton(fournisseur: &Fournisseur, secret: Option<Pkce>, demande: &Demande, auth: &AuthClient) -> Result<Pkce> {
+    match secret {
+        Some(pkce) if !pkce.is_expired() && pkce.demande() == demande => Ok(pkce),
+        _ => Pkce::new(fournisseur, demande, auth).await,
+    }
+}
+
+async fn appeler_api(
+    fournisseur: Fournisseur,
+    secret: Option<Pkce>,
+    demande: Demande,
+    auth: AuthClient,
+    requete: RequeteApi,
+) -> Result<(ReponseApi, Pkce)> {
+    let secret = jeton(&fournisseur, secret, &demande, &auth).await?;
+    let reponse = api::appeler(&requete, secret.secret(), secret.dpop())?;
+    Ok((reponse, secret))
+}
+
+async fn get_infos(fournisseur: Fournisseur, secret: Option<Pkce>, demande: Demande, auth: AuthClient) -> Result<(Option<TableData>, Option<Pkce>)> {
+    let secret = Some(jeton(&fournisseur, secret, &demande, &auth).await?);
 
-    let value = ureq::get(fournisseur.userinfos())
-        .set("Authorization", &format!("Bearer {}", secret.clone().unwrap().secret()))
-        .timeout(std::time::Duration::from_secs(20))
-        .call()?
-        .into_json::<Value>()?;
+    let pkce = secret.as_ref().expect("jeton obtenu");
+    let value = dpop::envoyer(
+        || ureq::get(fournisseur.userinfos()).timeout(std::time::Duration::from_secs(20)),
+        pkce.secret(),
+        pkce.dpop(),
+        None,
+    )?
+    .into_json::<Value>()?;
 
     match value {
         Value::Object(map) => {
@@ -162,7 +410,19 @@ pub fn main() {
         radio_fournisseur: Fournisseur::Microsoft,
         label_fournisseur: String::new(),
         secret: None,
+        scopes: [true, true, true, false],
+        autres_scopes: String::new(),
+        parametres: String::new(),
+        auth_clients: HashMap::new(),
+        client_credentials: ClientCredentials::default(),
+        requete_api: RequeteApi::default(),
+        titre_infos: String::new(),
         infos: Arc::new(TableData::default()),
+        ressource: Arc::new(TableData::default()),
+        titre_jeton: String::new(),
+        jeton: Arc::new(TableData::default()),
+        expiration: None,
+        liaison: String::new(),
         //        en_traitement: false,
         erreur: String::new(),
     };
@@ -1,12 +1,14 @@
+use crate::client::{client_oauth, parametres_assertion};
+use crate::dpop::{self, CleDpop};
 use crate::Fournisseur;
 use anyhow::{anyhow, Context, Error};
-use oauth2::basic::BasicClient;
-use oauth2::ureq::http_client;
-use oauth2::{
-    AccessToken, AuthType, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl, Scope, TokenResponse,
-    TokenUrl,
-};
-use std::io::{BufRead, BufReader, Write};
+use commun::assertion::{AuthClient, ModePar};
+use commun::autorisation::Demande;
+use commun::loopback::{self, Retour};
+use commun::{jarm, requete};
+use jsonwebtoken::jwk::JwkSet;
+use oauth2::{AccessToken, AuthorizationCode, CsrfToken, PkceCodeChallenge, RedirectUrl, Scope, TokenResponse};
+use serde_json::Value;
 use std::net::TcpListener;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{sync_channel, Receiver};
@@ -20,63 +22,114 @@ pub struct Pkce {
     token: AccessToken,
     creation: Instant,
     expired_in: Duration,
+    demande: Demande,
+    dpop: Option<CleDpop>,
 }
 
 impl Pkce {
-    pub async fn new(f: &Fournisseur) -> Result<Self, Error> {
-        let (id, secret) = f.secrets();
-        let id = ClientId::new(id.to_owned());
-        let secret = ClientSecret::new(secret.to_owned());
-
-        let (url_auth, url_token) = f.endpoints();
-        let url_auth = AuthUrl::new(url_auth.to_owned())?;
-        let url_token = TokenUrl::new(url_token.to_owned())?;
-
-        let client = BasicClient::new(id, Some(secret), url_auth, Some(url_token))
-            .set_auth_type(AuthType::RequestBody)
-            .set_redirect_uri(RedirectUrl::new("http://localhost:86".to_owned())?);
+    pub async fn new(f: &Fournisseur, demande: &Demande, auth: &AuthClient) -> Result<Self, Error> {
+        let client = client_oauth(f, auth)?.set_redirect_uri(RedirectUrl::new("http://localhost:86".to_owned())?);
 
         let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
 
-        let (authorize_url, csrf) = client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new("openid".to_owned()))
-            .add_scope(Scope::new("email".to_owned()))
-            .add_scope(Scope::new("profile".to_owned()))
-            .set_pkce_challenge(pkce_code_challenge)
+        let (authorize_url, csrf) = demande
+            .parametres
+            .iter()
+            .fold(
+                client
+                    .authorize_url(CsrfToken::new_random)
+                    .add_scopes(demande.scopes.iter().map(|s| Scope::new(s.to_owned())))
+                    .set_pkce_challenge(pkce_code_challenge),
+                |requete, (k, v)| requete.add_extra_param(k, v),
+            )
             .url();
 
+        let jarm = jarm::demande(&demande.parametres);
+        let (fournisseur, auth_requete) = (f.clone(), auth.clone());
+        let (authorize_url, document, jwks) = spawn_blocking(move || preparer(&fournisseur, &auth_requete, jarm, authorize_url)).await??;
+
+        let retour = Retour {
+            csrf: csrf.secret().to_owned(),
+            document,
+            jwks,
+            client_id: f.secrets().0.to_owned(),
+            jarm,
+        };
         let listener = TcpListener::bind("[::1]:86").context("TCP bind")?;
-        let (rx, stop_signal) = start_listening(listener, csrf)?;
+        let (rx, stop_signal) = start_listening(listener, retour)?;
         if let Err(e) = webbrowser::open(authorize_url.as_ref()).context("open browser") {
             stop_signal.store(true, Ordering::Relaxed);
             return Err(e);
         }
 
         let code = spawn_blocking(move || match rx.recv() {
-            Ok(code) => Ok(code),
+            Ok(code) => code,
             Err(_) => Err(anyhow!("Vous devez vous authentifier")),
         })
         .await??;
 
+        // L'assertion (exp à 5 minutes) est signée après l'authentification de l'utilisateur
+        let assertion = parametres_assertion(f, auth)?;
         let creation = Instant::now();
-        let token = client.exchange_code(code).set_pkce_verifier(pkce_code_verifier).request(http_client)?;
+        let dpop = auth.dpop.then(CleDpop::generer);
+        let token = assertion
+            .into_iter()
+            .fold(client.exchange_code(code).set_pkce_verifier(pkce_code_verifier), |requete, (k, v)| {
+                requete.add_extra_param(k, v)
+            })
+            .request(dpop::requete_jeton(dpop.as_ref()))?;
         let expired_in = token.expires_in().unwrap_or(Duration::from_secs(3600));
         let token = token.access_token().to_owned();
-        Ok(Self { token, creation, expired_in })
+        let demande = demande.clone();
+        Ok(Self {
+            token,
+            creation,
+            expired_in,
+            demande,
+            dpop,
+        })
     }
 
     pub fn is_expired(&self) -> bool {
         self.creation.elapsed() >= self.expired_in
     }
 
+    pub fn expire_dans(&self) -> Duration {
+        self.expired_in.saturating_sub(self.creation.elapsed())
+    }
+
     pub fn secret(&self) -> &String {
         self.token.secret()
     }
+
+    pub fn demande(&self) -> &Demande {
+        &self.demande
+    }
+
+    pub fn dpop(&self) -> Option<&CleDpop> {
+        self.dpop.as_ref()
+    }
 }
 
-fn start_listening(listener: TcpListener, csrf: CsrfToken) -> Result<(Receiver<AuthorizationCode>, Arc<AtomicBool>), Error> {
-    let (tx, rx) = sync_channel::<AuthorizationCode>(1);
+// Requête d'autorisation signée (JAR) puis poussée (PAR) selon les réglages du fournisseur.
+// Le document discovery n'est lu que si JAR, PAR ou JARM s'en servent, le JWKS seulement pour JARM; les appels à l'OP sont bloquants.
+fn preparer(f: &Fournisseur, auth: &AuthClient, jarm: bool, authorize_url: Url) -> Result<(Url, Option<Value>, Option<JwkSet>), Error> {
+    let document = (auth.jar || auth.par != ModePar::Jamais || jarm)
+        .then(|| requete::decouverte(f.discovery()))
+        .flatten();
+    let jwks = jarm.then(|| jarm::jwks(document.as_ref())).transpose()?;
+    let authorize_url = requete::signer(auth, document.as_ref(), authorize_url)?;
+    let (id, secret) = f.secrets();
+    let (_, url_token) = f.endpoints();
+    let authorize_url = requete::pousser(auth, id, secret, url_token, document.as_ref(), authorize_url)?;
+    Ok((authorize_url, document, jwks))
+}
+
+// Code d'autorisation, ou l'erreur affichée à l'utilisateur
+type Code = Result<AuthorizationCode, Error>;
+
+fn start_listening(listener: TcpListener, retour: Retour) -> Result<(Receiver<Code>, Arc<AtomicBool>), Error> {
+    let (tx, rx) = sync_channel::<Code>(1);
     let stop_signal = Arc::new(AtomicBool::new(false));
     let stop_signal2 = stop_signal.clone();
     listener.set_nonblocking(true).expect("Erreur set_nonblocking");
@@ -85,42 +138,18 @@ fn start_listening(listener: TcpListener, csrf: CsrfToken) -> Result<(Receiver<A
         let now = Instant::now();
         while !stop_signal2.load(Ordering::Relaxed) {
             match listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut request_line = String::new();
-                    let mut reader = BufReader::new(&stream);
-                    reader.read_line(&mut request_line).unwrap();
-
-                    let redirect_url = request_line.split_whitespace().nth(1).unwrap();
-                    let url = Url::parse(&(format!("http://localhost{redirect_url}"))).unwrap();
-                    let code_pair = url
-                        .query_pairs()
-                        .find(|pair| {
-                            let (key, _) = pair;
-                            key == "code"
-                        })
-                        .expect("Le code d'autorisation doit être présent");
-
-                    let (_, value) = code_pair;
-                    let code = AuthorizationCode::new(value.into_owned());
-
-                    let state_pair = url
-                        .query_pairs()
-                        .find(|pair| {
-                            let (key, _) = pair;
-                            key == "state"
-                        })
-                        .expect("Le jeton csrf doit être présent");
-
-                    let (_, value) = state_pair;
-                    assert_eq!(csrf.secret(), value.as_ref());
-
-                    let message = "<p>Retournez dans l'application &#128526;</p>";
-                    let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{message}", message.len());
-                    stream.write_all(response.as_bytes()).expect("Erreur write_all");
-
-                    let _ = tx.send(code);
-                    break;
-                }
+                // Le retour en fragment prend deux connexions: la page relais puis le POST des paramètres
+                Ok((stream, _)) => match loopback::traiter(stream, &retour) {
+                    Ok(None) => continue,
+                    Ok(Some(code)) => {
+                        let _ = tx.send(code.map(AuthorizationCode::new));
+                        break;
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(anyhow!("Réponse au navigateur: {e}")));
+                        break;
+                    }
+                },
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     if now.elapsed().as_secs() >= 150 {
                         break;
@@ -28,6 +28,14 @@ mod widget {
         }
     }
 
+    // La première ligne est l'entête
+    impl From<Vec<Vec<String>>> for TableData {
+        fn from(mut lignes: Vec<Vec<String>>) -> Self {
+            let header = if lignes.is_empty() { Vec::new() } else { lignes.remove(0) };
+            TableData { header, rows: lignes }
+        }
+    }
+
     fn rgba_f64(Color { r, g, b, a }: Color) -> (f64, f64, f64, f64) {
         (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, a as f64 / 255.0)
     }
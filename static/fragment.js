@@ -0,0 +1,23 @@
+// response_mode=fragment: la réponse d'autorisation n'est pas transmise au serveur par le navigateur,
+// elle est postée à /auth en formulaire
+document.addEventListener("DOMContentLoaded", () => {
+    const parametres = new URLSearchParams(window.location.hash.substring(1));
+    history.replaceState(null, "", window.location.pathname);
+    if (parametres.size === 0) {
+        document.body.textContent = "Réponse d'autorisation absente";
+        return;
+    }
+
+    const formulaire = document.createElement("form");
+    formulaire.method = "POST";
+    formulaire.action = "/auth";
+    for (const [nom, valeur] of parametres) {
+        const champ = document.createElement("input");
+        champ.type = "hidden";
+        champ.name = nom;
+        champ.value = valeur;
+        formulaire.appendChild(champ);
+    }
+    document.body.appendChild(formulaire);
+    formulaire.submit();
+});
@@ -0,0 +1,401 @@
+// La page est mise à jour par le DOM (textContent), sans évaluation de code: la CSP n'a pas besoin de 'unsafe-eval'
+const $ = (id) => document.getElementById(id);
+
+const afficher = (element, visible) => {
+    element.hidden = !visible;
+};
+
+// Une ligne par élément, une cellule par colonne
+const remplirTable = (tbody, lignes, colonnes) => {
+    tbody.replaceChildren(...lignes.map(ligne => {
+        const tr = document.createElement("tr");
+        tr.className = "mode";
+        for (const colonne of colonnes) {
+            const td = document.createElement("td");
+            td.textContent = ligne[colonne];
+            tr.appendChild(td);
+        }
+        return tr;
+    }));
+};
+
+// m:ss
+const formaterDuree = (restant) => Math.floor(restant / 60) + ":" + String(restant % 60).padStart(2, "0");
+
+const texte = (valeur) => typeof valeur === "string" ? valeur : JSON.stringify(valeur);
+
+// Les erreurs du serveur sont au format application/problem+json (RFC 7807)
+const lireReponse = (response) => {
+    if (response.ok) {
+        return response.json();
+    }
+    return response.json()
+        .catch(() => ({ detail: response.statusText }))
+        .then(probleme => {
+            throw new Error((probleme.detail ?? probleme.title) + " (" + response.status + ")");
+        });
+};
+
+// Double soumission: le jeton du cookie Csrf-Token est renvoyé dans l'entête X-Csrf-Token
+const entetesCsrf = () => {
+    const headers = new Headers({
+        'Content-Type': 'application/json',
+    });
+
+    const csrfCookie =
+        document.cookie
+        .split(';')
+        .find((item) => /^(__Host-)?Csrf-Token=/.test(item.trim()));
+
+    if (csrfCookie) {
+        headers.set('X-Csrf-Token', csrfCookie.split('=')[1])
+    }
+    return headers;
+};
+
+const scopesStandards = ["openid", "email", "profile", "offline_access"];
+
+const parametres = [
+    { nom: "prompt", exemple: "none | login | consent | select_account" },
+    { nom: "login_hint", exemple: "usager@exemple.com" },
+    { nom: "max_age", exemple: "0" },
+    { nom: "acr_values", exemple: "urn:mace:incommon:iap:silver" },
+    { nom: "ui_locales", exemple: "fr-CA en" },
+    { nom: "claims", exemple: '{"id_token":{"auth_time":{"essential":true}}}' },
+    { nom: "response_mode", exemple: "query | fragment | form_post | jwt | form_post.jwt" },
+];
+
+const caseScope = (scope) => $("scopes").querySelector('input[value="' + scope + '"]');
+const champParametre = (nom) => $("parametres").querySelector('input[name="' + nom + '"]');
+
+const fournisseur = () => document.querySelector('input[name="fournisseurs"]:checked').value;
+
+const choisirFournisseur = (valeur) => {
+    const radio = document.querySelector('input[name="fournisseurs"][value="' + valeur + '"]');
+    if (radio) {
+        radio.checked = true;
+    }
+    for (const span of document.querySelectorAll(".fournisseur")) {
+        span.textContent = fournisseur();
+    }
+};
+
+const scopes = () => scopesStandards.filter(scope => caseScope(scope).checked);
+
+// Corps JSON de /userinfos : fournisseur, origine, scopes et paramètres d'autorisation non vides
+const demande = () => {
+    const demande = {
+        fournisseur: fournisseur(),
+        origine: location.origin,
+        scopes: scopes().concat($("autres-scopes").value.split(/\s+/).filter(s => s)).join(" "),
+    };
+    for (const p of parametres) {
+        const valeur = champParametre(p.nom).value;
+        if (valeur) {
+            demande[p.nom] = valeur;
+        }
+    }
+    return demande;
+};
+
+// Les boutons sont désactivés pendant un appel
+const activer = (actif) => {
+    for (const id of ["bouton-userinfos", "bouton-client_credentials", "bouton-api"]) {
+        $(id).disabled = !actif;
+    }
+};
+
+const erreurFetch = (error) => {
+    $("erreur").textContent = error ? String(error) : "";
+};
+
+const afficherUserInfos = (proprietes) => {
+    remplirTable($("userinfos"), proprietes, ["propriété", "valeur"]);
+    afficher($("section-userinfos"), proprietes.length > 0);
+};
+
+const jeton = {
+    expiration: 0,
+    minuterie: null,
+};
+
+const effacerJeton = () => {
+    clearInterval(jeton.minuterie);
+    $("jeton-format").textContent = "";
+    $("jeton-compteur").textContent = "";
+    remplirTable($("jeton-claims").tBodies[0], [], []);
+    afficher($("jeton-claims"), false);
+    afficher($("jeton-opaque"), false);
+    afficher($("jeton-dpop"), false);
+    afficher($("section-jeton"), false);
+};
+
+const clicFournisseur = () => {
+    afficherUserInfos([]);
+    effacerJeton();
+    choisirFournisseur(fournisseur());
+    sessionStorage.setItem("fournisseur", fournisseur());
+};
+
+const changerMode = () => {
+    const mode = document.querySelector('input[name="modes"]:checked').value;
+    afficher($("mode-code"), mode === "code");
+    afficher($("bouton-userinfos"), mode === "code");
+    afficher($("mode-client_credentials"), mode === "client_credentials");
+    afficher($("bouton-client_credentials"), mode === "client_credentials");
+};
+
+const actions = {};
+
+actions.getUserInfos = () => {
+    activer(false);
+    erreurFetch("");
+
+    const request = new Request('/userinfos', {
+        method: 'POST',
+        headers: entetesCsrf(),
+        cache: 'no-cache',
+        redirect: 'error',
+        body: JSON.stringify(demande())
+    });
+
+    // La même demande doit être rejouée au retour du fournisseur
+    sessionStorage.setItem("demande", JSON.stringify({
+        scopes: scopes(),
+        autresScopes: $("autres-scopes").value,
+        parametres: parametres.map(p => champParametre(p.nom).value),
+    }));
+
+    fetch(request)
+    .then(lireReponse)
+    .then(data => {
+        if (data.hasOwnProperty("redirectOP")) {
+            sessionStorage.setItem("actionAfterAuth", "getUserInfos");
+            window.location.replace(data.redirectOP);
+        } else {
+            afficherUserInfos(data);
+            activer(true);
+        }
+    })
+    .catch((error) => {
+        console.log("Erreur Fetch: " + error);
+        erreurFetch(error);
+        afficherUserInfos([]);
+        activer(true);
+    });
+};
+
+actions.getClientCredentials = () => {
+    activer(false);
+    erreurFetch("");
+    remplirTable($("cc-jeton"), [], []);
+    afficher($("section-client_credentials"), false);
+    afficher($("cc-ressource"), false);
+
+    const request = new Request('/client_credentials', {
+        method: 'POST',
+        headers: entetesCsrf(),
+        cache: 'no-cache',
+        redirect: 'error',
+        body: JSON.stringify({
+            fournisseur: fournisseur(),
+            scopes: $("cc-scopes").value,
+            resource: $("cc-resource").value,
+            url: $("cc-url").value,
+        })
+    });
+
+    fetch(request)
+    .then(lireReponse)
+    .then(data => {
+        const proprietes = data.jeton.map(p => ({ propriété: p.propriété, valeur: texte(p.valeur) }));
+        remplirTable($("cc-jeton"), proprietes, ["propriété", "valeur"]);
+        afficher($("section-client_credentials"), proprietes.length > 0);
+        if (data.ressource) {
+            $("cc-ressource-statut").textContent = data.ressource.statut ?? data.ressource.erreur;
+            $("cc-ressource-corps").textContent = JSON.stringify(data.ressource.corps ?? "", null, 2);
+            afficher($("cc-ressource"), true);
+        }
+        activer(true);
+    })
+    .catch((error) => {
+        console.log("Erreur Fetch: " + error);
+        erreurFetch(error);
+        activer(true);
+    });
+};
+
+actions.appelerApi = () => {
+    activer(false);
+    erreurFetch("");
+    afficher($("api-reponse"), false);
+
+    const request = new Request('/api', {
+        method: 'POST',
+        headers: entetesCsrf(),
+        cache: 'no-cache',
+        redirect: 'error',
+        body: JSON.stringify({
+            methode: $("api-methode").value,
+            url: $("api-url").value,
+            entetes: $("api-entetes").value,
+            corps: $("api-corps").value,
+        })
+    });
+
+    fetch(request)
+    .then(lireReponse)
+    .then(data => {
+        const objet = data.corps !== null && typeof data.corps === "object" && !Array.isArray(data.corps);
+        const corps = objet ? Object.entries(data.corps).map(([k, v]) => ({ propriété: k, valeur: texte(v) })) : [];
+        const brut = objet || data.corps === undefined ? "" : JSON.stringify(data.corps, null, 2);
+
+        $("api-statut").textContent = data.statut ?? data.erreur;
+        remplirTable($("api-reponse-entetes"), (data.entêtes ?? []).map(p => ({ propriété: p.propriété, valeur: texte(p.valeur) })), ["propriété", "valeur"]);
+        remplirTable($("api-reponse-corps").tBodies[0], corps, ["propriété", "valeur"]);
+        afficher($("api-reponse-corps"), corps.length > 0);
+        $("api-reponse-brut").textContent = brut;
+        afficher($("api-reponse-brut"), brut.length > 0);
+        afficher($("api-reponse"), true);
+        activer(true);
+    })
+    .catch((error) => {
+        console.log("Erreur Fetch: " + error);
+        erreurFetch(error);
+        activer(true);
+    });
+};
+
+actions.getJeton = () => {
+    erreurFetch("");
+
+    fetch(new Request('/token', { method: 'GET', cache: 'no-cache', redirect: 'error' }))
+    .then(lireReponse)
+    .then(data => {
+        effacerJeton();
+        $("jeton-format").textContent = data.format;
+        afficher($("jeton-opaque"), data.format === "opaque");
+        // Jeton DPoP: cnf.jkt doit être l'empreinte de la clé de la session
+        if (data.jkt) {
+            $("jeton-jkt").textContent = data.jkt;
+            $("jeton-liaison").textContent = data["cnf.jkt"] === undefined ? "cnf.jkt absent du jeton" : data["cnf.jkt"] === data.jkt ? "cnf.jkt correspond" : "cnf.jkt différent : " + data["cnf.jkt"];
+            afficher($("jeton-dpop"), true);
+        }
+        if (data.format === "JWT") {
+            const claims = [];
+            for (const [partie, valeurs] of [["entête", data["entête"]], ["charge", data.charge]]) {
+                for (const [claim, valeur] of Object.entries(valeurs)) {
+                    claims.push({ partie: partie, claim: claim, valeur: texte(valeur) });
+                }
+            }
+            remplirTable($("jeton-claims").tBodies[0], claims, ["partie", "claim", "valeur"]);
+            afficher($("jeton-claims"), claims.length > 0);
+        }
+        afficher($("section-jeton"), true);
+
+        // exp fait foi pour un JWT, sinon la durée de vie annoncée par le serveur
+        jeton.expiration = typeof data.exp === "number" ? data.exp * 1000 : Date.now() + data.expireDans * 1000;
+        const majCompteur = () => {
+            const restant = Math.max(0, Math.floor((jeton.expiration - Date.now()) / 1000));
+            $("jeton-compteur").textContent = restant > 0 ? formaterDuree(restant) : "expiré";
+        };
+        majCompteur();
+        jeton.minuterie = setInterval(majCompteur, 1000);
+    })
+    .catch((error) => {
+        console.log("Erreur Fetch: " + error);
+        erreurFetch(error);
+        effacerJeton();
+    });
+};
+
+// L'état de la session est poussé par le serveur (Server-Sent Events) jusqu'à ce qu'elle soit absente ou expirée
+const suivreStatut = () => {
+    const etats = {
+        aucune: ["Aucune session", "gris"],
+        en_attente: ["Authentification en cours", "bleu"],
+        authentifiee: ["Authentifiée", "vert"],
+        expire_bientot: ["Expire bientôt", "ambre"],
+        expiree: ["Expirée", "rouge"],
+    };
+    const source = new EventSource('/statut');
+    source.addEventListener("statut", (e) => {
+        const statut = JSON.parse(e.data);
+        const [libelle, classe] = etats[statut.etat];
+        $("statut").textContent = libelle;
+        $("statut").className = "etiquette " + classe;
+        $("statut-compteur").textContent = typeof statut.expireDans === "number" ? formaterDuree(statut.expireDans) : "";
+        // Valeurs de l'id_token visées par les déconnexions front-channel et back-channel
+        for (const [id, valeur] of [["statut-iss", statut.iss], ["statut-sid", statut.sid]]) {
+            $(id).querySelector("span").textContent = valeur || "";
+            afficher($(id), Boolean(valeur));
+        }
+        if (statut.etat === "aucune" || statut.etat === "expiree") {
+            source.close();
+        }
+    });
+};
+
+// Cases des scopes et champs des paramètres
+$("scopes").replaceChildren(...scopesStandards.flatMap(scope => {
+    const input = document.createElement("input");
+    input.className = "choix";
+    input.type = "checkbox";
+    input.name = "scopes";
+    input.value = scope;
+    input.checked = scope !== "offline_access";
+    const libelle = document.createElement("b");
+    libelle.textContent = scope;
+    return [input, libelle, document.createElement("br")];
+}));
+$("parametres").replaceChildren(...parametres.map(p => {
+    const tr = document.createElement("tr");
+    const nom = document.createElement("b");
+    nom.textContent = p.nom;
+    const input = document.createElement("input");
+    input.className = "champ";
+    input.type = "text";
+    input.name = p.nom;
+    input.placeholder = p.exemple;
+    for (const contenu of [nom, input]) {
+        tr.appendChild(document.createElement("td")).appendChild(contenu);
+    }
+    return tr;
+}));
+
+for (const radio of document.querySelectorAll('input[name="fournisseurs"]')) {
+    radio.addEventListener("change", clicFournisseur);
+}
+for (const radio of document.querySelectorAll('input[name="modes"]')) {
+    radio.addEventListener("change", changerMode);
+}
+$("bouton-userinfos").addEventListener("click", actions.getUserInfos);
+$("bouton-client_credentials").addEventListener("click", actions.getClientCredentials);
+$("bouton-api").addEventListener("click", actions.appelerApi);
+$("bouton-jeton").addEventListener("click", actions.getJeton);
+
+suivreStatut();
+
+const fournisseurSession = sessionStorage.getItem("fournisseur");
+if (fournisseurSession) {
+    choisirFournisseur(fournisseurSession);
+} else {
+    choisirFournisseur("Microsoft");
+    sessionStorage.setItem("fournisseur", fournisseur());
+}
+
+const demandeSession = JSON.parse(sessionStorage.getItem("demande"));
+if (demandeSession) {
+    for (const scope of scopesStandards) {
+        caseScope(scope).checked = demandeSession.scopes.includes(scope);
+    }
+    $("autres-scopes").value = demandeSession.autresScopes;
+    demandeSession.parametres.forEach((valeur, i) => champParametre(parametres[i].nom).value = valeur);
+}
+
+// Au retour du fournisseur, l'action qui a déclenché l'authentification est rejouée
+const actionAfterAuth = sessionStorage.getItem("actionAfterAuth");
+if (actionAfterAuth) {
+    sessionStorage.removeItem("actionAfterAuth");
+    actions[actionAfterAuth]();
+}